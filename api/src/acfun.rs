@@ -1,4 +1,4 @@
-use crate::response::*;
+use crate::{checked::AcFunChecked, response::*};
 use pretend::{pretend, Json, Response, Result};
 use serde::Serialize;
 
@@ -12,16 +12,46 @@ pub(crate) struct LoginForm<'a> {
 
 impl<'a> LoginForm<'a> {
     #[inline]
-    pub(crate) fn new(username: &'a str, password: &'a str) -> Self {
+    pub(crate) fn new(
+        username: &'a str,
+        password: &'a str,
+        key: &'a str,
+        captcha: &'a str,
+    ) -> Self {
         Self {
             username,
             password,
-            key: "",
-            captcha: "",
+            key,
+            captcha,
         }
     }
 }
 
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct SmsCodeForm<'a> {
+    mobile: &'a str,
+}
+
+impl<'a> SmsCodeForm<'a> {
+    #[inline]
+    pub(crate) fn new(mobile: &'a str) -> Self {
+        Self { mobile }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct SmsLoginForm<'a> {
+    mobile: &'a str,
+    code: &'a str,
+}
+
+impl<'a> SmsLoginForm<'a> {
+    #[inline]
+    pub(crate) fn new(mobile: &'a str, code: &'a str) -> Self {
+        Self { mobile, code }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Serialize)]
 pub(crate) enum Sid {
     #[serde(rename = "acfun.api.visitor")]
@@ -40,13 +70,59 @@ pub(crate) trait AcFunId {
     #[request(method = "POST", path = "/rest/web/login/signin")]
     async fn login(&self, form: &LoginForm) -> Result<Response<Json<Login>>>;
 
+    #[request(method = "POST", path = "/rest/web/login/sms/send")]
+    async fn sms_code(&self, form: &SmsCodeForm) -> Result<Json<SmsCode>>;
+
+    #[request(method = "POST", path = "/rest/web/login/sms/signin")]
+    async fn sms_login(&self, form: &SmsLoginForm) -> Result<Response<Json<Login>>>;
+
+    #[request(method = "POST", path = "/rest/web/login/captcha")]
+    async fn captcha(&self) -> Result<Json<AcFunChecked<Captcha>>>;
+
     #[request(method = "POST", path = "/rest/app/visitor/login")]
-    #[header(name = "Cookie", value = "_did={device_id}")]
-    async fn visitor_token(&self, form: TokenForm, device_id: &str) -> Result<Json<VisitorToken>>;
+    #[header(name = "X-AcFunLive-Idempotent", value = "1")]
+    async fn visitor_token(&self, form: TokenForm) -> Result<Json<AcFunChecked<VisitorToken>>>;
 
     #[request(method = "POST", path = "/rest/web/token/get")]
-    #[header(name = "Cookie", value = "{cookie}")]
-    async fn user_token(&self, form: TokenForm, cookie: &str) -> Result<Json<UserToken>>;
+    #[header(name = "X-AcFunLive-Idempotent", value = "1")]
+    async fn user_token(&self, form: TokenForm) -> Result<Json<AcFunChecked<UserToken>>>;
+}
+
+#[pretend]
+pub(crate) trait AcFunScan {
+    #[request(method = "GET", path = "/rest/pc-direct/qr/start?type=WEB_LOGIN")]
+    async fn qr_start(&self) -> Result<Json<QrLogin>>;
+
+    #[request(
+        method = "GET",
+        path = "/rest/pc-direct/qr/scanResult?qrLoginToken={token}&qrLoginSignature={signature}"
+    )]
+    async fn qr_scan_result(&self, token: &str, signature: &str) -> Result<Json<QrScanResult>>;
+
+    #[request(
+        method = "GET",
+        path = "/rest/pc-direct/qr/acceptResult?qrLoginToken={token}&qrLoginSignature={signature}"
+    )]
+    async fn qr_accept_result(
+        &self,
+        token: &str,
+        signature: &str,
+    ) -> Result<Response<Json<QrAcceptResult>>>;
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct LiveListQuery<'a> {
+    pub(crate) count: u32,
+    pub(crate) pcursor: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) filters: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FilterQuery {
+    pub(crate) filter_type: i32,
+    pub(crate) filter_id: i32,
 }
 
 #[pretend]
@@ -58,21 +134,23 @@ pub(crate) trait AcFunLive {
         method = "GET",
         path = "/api/channel/list?count={count}&pcursor={page}"
     )]
-    #[header(name = "Cookie", value = "{cookie}")]
-    async fn live_list(&self, count: u32, page: u32, cookie: &str) -> Result<Json<LiveList>>;
+    async fn live_list(&self, count: u32, page: u32) -> Result<Json<AcFunChecked<LiveList>>>;
+
+    #[request(method = "GET", path = "/api/channel/list")]
+    async fn live_list_page(
+        &self,
+        query: &LiveListQuery<'_>,
+    ) -> Result<Json<AcFunChecked<LiveList>>>;
 
     #[request(method = "GET", path = "/rest/pc-direct/fansClub/fans/medal/list")]
-    #[header(name = "Cookie", value = "{cookie}")]
-    async fn medal_list(&self, cookie: &str) -> Result<Json<MedalList>>;
+    async fn medal_list(&self) -> Result<Json<AcFunChecked<MedalList>>>;
 
     #[request(method = "GET", path = "/api/live/info?authorId={liver_uid}")]
-    #[header(name = "Cookie", value = "{cookie}")]
-    async fn live_info(&self, liver_uid: i64, cookie: &str) -> Result<Json<UserLiveInfo>>;
+    async fn live_info(&self, liver_uid: i64) -> Result<Json<AcFunChecked<UserLiveInfo>>>;
 
     #[request(
         method = "GET",
         path = "/rest/pc-direct/fansClub/friendshipDegreeRankInfo?uperId={liver_uid}"
     )]
-    #[header(name = "Cookie", value = "{cookie}")]
-    async fn medal_rank_list(&self, liver_uid: i64, cookie: &str) -> Result<Json<MedalRankList>>;
+    async fn medal_rank_list(&self, liver_uid: i64) -> Result<Json<AcFunChecked<MedalRankList>>>;
 }
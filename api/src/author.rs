@@ -0,0 +1,233 @@
+use crate::{checked::KsChecked, client::ApiClient, kuaishou::KsQuery, response::*, Error, Result};
+use pretend::{pretend, Json};
+use serde::Serialize;
+use std::borrow::Cow;
+
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct LiveSettings {
+    pub title: String,
+    pub cover: String,
+    pub portrait: bool,
+    pub panoramic: bool,
+    pub live_type: Option<LiveType>,
+}
+
+impl LiveSettings {
+    #[inline]
+    pub fn new<'a>(title: impl Into<Cow<'a, str>>) -> Self {
+        Self {
+            title: title.into().into_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn cover<'a>(mut self, cover: impl Into<Cow<'a, str>>) -> Self {
+        self.cover = cover.into().into_owned();
+
+        self
+    }
+
+    #[inline]
+    pub const fn portrait(mut self, portrait: bool) -> Self {
+        self.portrait = portrait;
+
+        self
+    }
+
+    #[inline]
+    pub const fn panoramic(mut self, panoramic: bool) -> Self {
+        self.panoramic = panoramic;
+
+        self
+    }
+
+    #[inline]
+    pub fn live_type(mut self, live_type: LiveType) -> Self {
+        self.live_type = Some(live_type);
+
+        self
+    }
+
+    #[inline]
+    fn biz_custom_data(&self) -> String {
+        match &self.live_type {
+            Some(t) => format!(
+                r#"{{"typeId":{},"type":[{},{}]}}"#,
+                t.id, t.category_id, t.id
+            ),
+            None => String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StartPushForm<'a> {
+    caption: &'a str,
+    cover: &'a str,
+    stream_name: &'a str,
+    portrait: bool,
+    panoramic: bool,
+    biz_custom_data: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StopPushForm<'a> {
+    live_id: &'a str,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangeCaptionForm<'a> {
+    live_id: &'a str,
+    caption: &'a str,
+    cover: &'a str,
+}
+
+#[pretend]
+trait KuaishouZtAuthor {
+    #[request(method = "POST", path = "/rest/zt/live/web/obs/config")]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn push_config(&self, query: &KsQuery) -> pretend::Result<Json<KsChecked<PushConfig>>>;
+
+    #[request(method = "POST", path = "/rest/zt/live/web/obs/status")]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn live_status(
+        &self,
+        query: &KsQuery,
+    ) -> pretend::Result<Json<KsChecked<AuthorLiveStatus>>>;
+
+    #[request(method = "POST", path = "/rest/zt/live/web/obs/startPush")]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn start_push(
+        &self,
+        query: &KsQuery,
+        form: &StartPushForm,
+    ) -> pretend::Result<Json<KsChecked<StartLive>>>;
+
+    #[request(method = "POST", path = "/rest/zt/live/web/obs/stopPush")]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn stop_push(
+        &self,
+        query: &KsQuery,
+        form: &StopPushForm,
+    ) -> pretend::Result<Json<KsChecked<StopLive>>>;
+
+    #[request(method = "POST", path = "/rest/zt/live/web/obs/changeCaption")]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn change_caption(
+        &self,
+        query: &KsQuery,
+        form: &ChangeCaptionForm,
+    ) -> pretend::Result<Json<KsChecked<ActionResult>>>;
+}
+
+impl<C> ApiClient<C>
+where
+    C: pretend::client::Client + Send + Sync,
+{
+    #[inline]
+    pub async fn get_push_config(&self) -> Result<PushConfig> {
+        if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            self.kuaishou_zt()
+                .push_config(&self.ks_query())
+                .await?
+                .value()
+                .into_result()
+        }
+    }
+
+    #[inline]
+    pub async fn get_author_live_status(&self) -> Result<AuthorLiveStatus> {
+        if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            self.kuaishou_zt()
+                .live_status(&self.ks_query())
+                .await?
+                .value()
+                .into_result()
+        }
+    }
+
+    pub async fn start_live(
+        &self,
+        stream_name: impl Into<Cow<'_, str>>,
+        settings: &LiveSettings,
+    ) -> Result<StartLive> {
+        let stream_name = stream_name.into();
+        if stream_name.is_empty() {
+            Err(Error::EmptyStreamName)
+        } else if settings.title.is_empty() {
+            Err(Error::EmptyLiveTitle)
+        } else if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            self.kuaishou_zt()
+                .start_push(
+                    &self.ks_query(),
+                    &StartPushForm {
+                        caption: &settings.title,
+                        cover: &settings.cover,
+                        stream_name: &stream_name,
+                        portrait: settings.portrait,
+                        panoramic: settings.panoramic,
+                        biz_custom_data: settings.biz_custom_data(),
+                    },
+                )
+                .await?
+                .value()
+                .into_result()
+        }
+    }
+
+    #[inline]
+    pub async fn stop_live(&self, live_id: impl Into<Cow<'_, str>>) -> Result<StopLive> {
+        let live_id = live_id.into();
+        if live_id.is_empty() {
+            Err(Error::EmptyLiveId)
+        } else if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            self.kuaishou_zt()
+                .stop_push(&self.ks_query(), &StopPushForm { live_id: &live_id })
+                .await?
+                .value()
+                .into_result()
+        }
+    }
+
+    pub async fn change_title_and_cover(
+        &self,
+        live_id: impl Into<Cow<'_, str>>,
+        title: impl Into<Cow<'_, str>>,
+        cover: impl Into<Cow<'_, str>>,
+    ) -> Result<ActionResult> {
+        let live_id = live_id.into();
+        let title = title.into();
+        if live_id.is_empty() {
+            Err(Error::EmptyLiveId)
+        } else if title.is_empty() {
+            Err(Error::EmptyLiveTitle)
+        } else if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            self.kuaishou_zt()
+                .change_caption(
+                    &self.ks_query(),
+                    &ChangeCaptionForm {
+                        live_id: &live_id,
+                        caption: &title,
+                        cover: &cover.into(),
+                    },
+                )
+                .await?
+                .value()
+                .into_result()
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{Error, Result};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use serde_json::Value;
+use std::convert::TryFrom;
+
+pub(crate) const ACFUN_RESULT_OK: i32 = 0;
+pub(crate) const KUAISHOU_RESULT_OK: i32 = 1;
+
+pub(crate) type AcFunChecked<T> = Checked<T, ACFUN_RESULT_OK>;
+pub(crate) type KsChecked<T> = Checked<T, KUAISHOU_RESULT_OK>;
+
+/// A response which is deserialized only if its `result` is `OK`, otherwise it's an `Error::Api`.
+///
+/// The result codes of AcFun and Kuaishou aren't documented, so they aren't mapped to dedicated
+/// errors here, `ApiClient` derives them from the endpoint or another request instead.
+#[derive(Debug)]
+pub(crate) struct Checked<T, const OK: i32>(Result<T>);
+
+impl<T, const OK: i32> Checked<T, OK> {
+    #[inline]
+    pub(crate) fn into_result(self) -> Result<T> {
+        self.0
+    }
+}
+
+impl<'de, T, const OK: i32> Deserialize<'de> for Checked<T, OK>
+where
+    T: DeserializeOwned,
+{
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        // the result of the live list is in `channelListData`
+        let code = value
+            .get("result")
+            .or_else(|| value.pointer("/channelListData/result"))
+            .and_then(Value::as_i64);
+        match code {
+            Some(code) if code != i64::from(OK) => {
+                let code = i32::try_from(code).map_err(|_| {
+                    serde::de::Error::custom(format!("the result code {} is out of range", code))
+                })?;
+                let message = ["error_msg", "errorMsg", "message"]
+                    .iter()
+                    .find_map(|key| value.get(*key)?.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                Ok(Self(Err(Error::Api { code, message })))
+            }
+            _ => T::deserialize(value)
+                .map(|t| Self(Ok(t)))
+                .map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::*;
+
+    #[test]
+    fn test_checked() -> Result<()> {
+        let gifts: KsChecked<GiftList> =
+            serde_json::from_str(include_str!("../fixtures/gift_list.json"))?;
+        assert_eq!(gifts.into_result()?.result, KUAISHOU_RESULT_OK);
+        let live_list: AcFunChecked<LiveList> =
+            serde_json::from_str(include_str!("../fixtures/live_list.json"))?;
+        assert!(live_list.into_result().is_ok());
+
+        let error: KsChecked<GiftList> =
+            serde_json::from_str(r#"{"result": 10, "error_msg": "unknown error"}"#)?;
+        assert!(matches!(
+            error.into_result(),
+            Err(Error::Api { code: 10, message }) if message == "unknown error"
+        ));
+        let error: AcFunChecked<LiveList> =
+            serde_json::from_str(r#"{"channelListData": {"result": 10}}"#)?;
+        assert!(matches!(
+            error.into_result(),
+            Err(Error::Api { code: 10, .. })
+        ));
+        assert!(serde_json::from_str::<KsChecked<GiftList>>(r#"{"result": 4294967297}"#).is_err());
+
+        Ok(())
+    }
+}
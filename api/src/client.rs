@@ -1,17 +1,41 @@
-use crate::{acfun::*, kuaishou::*, response::*, Error, Rest, Result};
-use cookie::Cookie;
+use crate::{
+    acfun::*,
+    jar::{CookieClient, CookieJar},
+    kuaishou::*,
+    response::*,
+    retry::{RateLimiter, RetryClient, RetryPolicy},
+    store::TokenStore,
+    stream::StreamSelector,
+    Error, Rest, Result,
+};
 use core::str;
-use pretend::{http::header::SET_COOKIE, resolver::UrlResolver, Pretend, Response, Url};
+use futures_timer::Delay;
+use pretend::{resolver::UrlResolver, Pretend, Response, Url};
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 #[cfg(feature = "default_http_client")]
 use crate::http::{new_http_client, HttpClient};
 
 const ACFUN_ID: &str = "https://id.app.acfun.cn/";
 const ACFUN_LIVE: &str = "https://live.acfun.cn/";
+const ACFUN_SCAN: &str = "https://scan.acfun.cn/";
 const KUAISHOU_ZT: &str = "https://api.kuaishouzt.com/";
 //const ACFUN_MEMBER: &str = "https://member.acfun.cn/";
+const ACFUN_DOMAIN: &str = "acfun.cn";
+const DEVICE_ID_COOKIE: &str = "_did";
+
+const LOGIN_RESULT_WRONG_PASSWORD: i32 = 310_201;
+const LOGIN_RESULT_CAPTCHA_REQUIRED: i32 = 310_202;
+const LOGIN_RESULT_WRONG_CAPTCHA: i32 = 310_203;
+const LOGIN_RESULT_ACCOUNT_LOCKED: i32 = 310_204;
+
+const QR_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 pub type Cookies = String;
 
@@ -21,26 +45,47 @@ pub enum AcFunToken {
     User(UserToken),
 }
 
-#[derive(Clone, Debug)]
+type ApiPretend<C> = Pretend<CookieClient<RetryClient<C>>, UrlResolver>;
+
+#[derive(Debug)]
 struct Clients<C> {
-    acfun_id: Pretend<C, UrlResolver>,
-    acfun_live: Pretend<C, UrlResolver>,
-    kuaishou_zt: Pretend<C, UrlResolver>,
-    //acfun_member: Pretend<C, UrlResolver>,
+    acfun_id: ApiPretend<C>,
+    acfun_live: ApiPretend<C>,
+    acfun_scan: ApiPretend<C>,
+    kuaishou_zt: ApiPretend<C>,
+    //acfun_member: ApiPretend<C>,
+    jar: CookieJar,
+    retry: RetryClient<C>,
 }
 
 impl<C: Clone> Clients<C> {
     #[inline]
     fn new(client: C) -> Result<Self> {
+        Self::with_jar(RetryClient::new(client), CookieJar::new())
+    }
+
+    fn with_jar(retry: RetryClient<C>, jar: CookieJar) -> Result<Self> {
+        let client = CookieClient::new(retry.clone(), jar.clone());
         Ok(Self {
             acfun_id: Pretend::for_client(client.clone()).with_url(Url::parse(ACFUN_ID)?),
             acfun_live: Pretend::for_client(client.clone()).with_url(Url::parse(ACFUN_LIVE)?),
+            acfun_scan: Pretend::for_client(client.clone()).with_url(Url::parse(ACFUN_SCAN)?),
             kuaishou_zt: Pretend::for_client(client).with_url(Url::parse(KUAISHOU_ZT)?),
             //acfun_member: Pretend::for_client(client.clone()).with_url(Url::parse(ACFUN_MEMBER)?),
+            jar,
+            retry,
         })
     }
 }
 
+/// Clones get a copy of the cookies, so signing in or setting the token of a clone doesn't change
+/// the cookies of the others.
+impl<C: Clone> Clone for Clients<C> {
+    fn clone(&self) -> Self {
+        Self::with_jar(self.retry.clone(), self.jar.deep_clone()).expect("invalid API URL")
+    }
+}
+
 #[cfg(feature = "default_http_client")]
 impl Clients<HttpClient> {
     #[inline]
@@ -84,6 +129,14 @@ pub struct Stream {
     pub bitrate: i32,
     pub quality_type: String,
     pub quality_name: String,
+    #[serde(default)]
+    pub media_type: String,
+    #[serde(default)]
+    pub level: i32,
+    #[serde(default)]
+    pub hidden: bool,
+    #[serde(default)]
+    pub default_select: bool,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
@@ -96,7 +149,18 @@ pub struct Live {
     pub start_time: i64,
     pub panoramic: bool,
     pub stream_name: String,
+    /// The representations of the first manifest in `stream_info`.
     pub stream_list: Vec<Stream>,
+    /// All manifests of the live, see `StreamSelector` for selecting a stream among them.
+    #[serde(default)]
+    pub stream_info: StreamInfo,
+}
+
+impl Live {
+    #[inline]
+    pub fn select_stream(&self, selector: &StreamSelector) -> Option<Stream> {
+        selector.select_stream(&self.stream_info)
+    }
 }
 
 #[cfg(feature = "default_http_client")]
@@ -137,8 +201,19 @@ impl<C> ApiClient<C> {
         })
     }
 
+    /// Sets the token and replaces the cookies in the cookie jar with the device ID and cookies in the token.
     #[inline]
     pub fn set_token(&mut self, token: ApiToken) -> &mut Self {
+        self.clients.jar.clear();
+        if !token.device_id.is_empty() {
+            self.clients.jar.add_cookies(
+                ACFUN_DOMAIN,
+                &format!("{}={}", DEVICE_ID_COOKIE, token.device_id),
+            );
+        }
+        if let Some(cookies) = &token.cookies {
+            self.clients.jar.add_cookies(ACFUN_DOMAIN, cookies);
+        }
         self.user_id_string = token.user_id.to_string();
         self.token = token;
         self
@@ -149,6 +224,7 @@ impl<C> ApiClient<C> {
         &self.token
     }
 
+    /// Changes of the device ID and cookies aren't applied to the cookie jar, use `set_token()` instead.
     #[inline]
     pub fn token_mut(&mut self) -> &mut ApiToken {
         &mut self.token
@@ -175,18 +251,60 @@ impl<C> ApiClient<C> {
         self.live.is_some()
     }
 
+    /// The cookie jar shared by all HTTP clients of this client, clones of this client have their own copies.
+    #[inline]
+    pub const fn cookie_jar(&self) -> &CookieJar {
+        &self.clients.jar
+    }
+
+    /// Sets the retry policy of idempotent requests, shared by clones of this client.
+    #[inline]
+    pub fn set_retry_policy(&mut self, policy: Option<RetryPolicy>) -> &mut Self {
+        let _ = self.clients.retry.set_policy(policy);
+        self
+    }
+
+    #[inline]
+    pub fn retry_policy(&self) -> Option<RetryPolicy> {
+        self.clients.retry.policy()
+    }
+
+    /// The rate limiter of requests to each host, shared by clones of this client.
     #[inline]
-    pub const fn acfun_id(&self) -> &Pretend<C, UrlResolver> {
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        self.clients.retry.rate_limiter()
+    }
+
+    /// The cookies of the signed-in user in the cookie jar, which were set by the response from the URL.
+    fn user_cookies(&self, url: &str) -> Result<Cookies> {
+        Ok(self
+            .clients
+            .jar
+            .cookies(&Url::parse(url)?)
+            .unwrap_or_default()
+            .split("; ")
+            .filter(|c| !c.is_empty() && !c.starts_with(&format!("{}=", DEVICE_ID_COOKIE)))
+            .collect::<Vec<_>>()
+            .join("; "))
+    }
+
+    #[inline]
+    pub const fn acfun_id(&self) -> &ApiPretend<C> {
         &self.clients.acfun_id
     }
 
     #[inline]
-    pub const fn acfun_live(&self) -> &Pretend<C, UrlResolver> {
+    pub const fn acfun_live(&self) -> &ApiPretend<C> {
         &self.clients.acfun_live
     }
 
     #[inline]
-    pub const fn kuaishou_zt(&self) -> &Pretend<C, UrlResolver> {
+    pub const fn acfun_scan(&self) -> &ApiPretend<C> {
+        &self.clients.acfun_scan
+    }
+
+    #[inline]
+    pub const fn kuaishou_zt(&self) -> &ApiPretend<C> {
         &self.clients.kuaishou_zt
     }
 
@@ -221,7 +339,7 @@ impl<C> ApiClient<C> {
     }
 
     #[inline]
-    fn ks_query(&self) -> KsQuery {
+    pub(crate) fn ks_query(&self) -> KsQuery<'_> {
         if self.is_visitor() {
             KsQuery::visitor(
                 &self.user_id_string,
@@ -247,92 +365,312 @@ impl<C> ApiClient<C>
 where
     C: pretend::client::Client + Send + Sync,
 {
+    #[inline]
     pub async fn user(
         &self,
         account: impl Into<Cow<'_, str>>,
         password: impl Into<Cow<'_, str>>,
+    ) -> Result<(Login, Cookies)> {
+        self.user_with_captcha(account, password, "", "").await
+    }
+
+    /// Signs in with the captcha solved from `Error::CaptchaRequired`, `Error::WrongCaptcha` or `get_captcha()`.
+    pub async fn user_with_captcha(
+        &self,
+        account: impl Into<Cow<'_, str>>,
+        password: impl Into<Cow<'_, str>>,
+        key: impl Into<Cow<'_, str>>,
+        captcha: impl Into<Cow<'_, str>>,
     ) -> Result<(Login, Cookies)> {
         let resp: Response<_> = self
             .acfun_id()
-            .login(&LoginForm::new(&account.into(), &password.into()))
+            .login(&LoginForm::new(
+                &account.into(),
+                &password.into(),
+                &key.into(),
+                &captcha.into(),
+            ))
             .await?;
 
-        let cookies = resp
-            .headers()
-            .get_all(SET_COOKIE)
-            .iter()
-            .map(|v| {
-                v.to_str()
-                    .map_err(Error::HeaderToStrError)
-                    .and_then(|s| Cookie::parse(s).map_err(Error::ParseCookieError))
-                    .map(|c| format!("{}={}", c.name(), c.value()))
+        let login = self.check_login(resp.into_body().value()).await?;
+
+        Ok((login, self.user_cookies(ACFUN_ID)?))
+    }
+
+    /// Sends the SMS code for signing in to the mobile phone.
+    pub async fn send_sms_code(&self, mobile: impl Into<Cow<'_, str>>) -> Result<()> {
+        let code = self
+            .acfun_id()
+            .sms_code(&SmsCodeForm::new(&mobile.into()))
+            .await?
+            .value();
+        if code.result == 0 {
+            Ok(())
+        } else {
+            Err(Error::LoginFailed {
+                code: code.result,
+                message: code.error_msg,
             })
-            .collect::<std::result::Result<Vec<_>, _>>()?
-            .join("; ");
-        let login: Login = resp.into_body().value();
+        }
+    }
+
+    /// Signs in with the mobile phone and the SMS code sent by `send_sms_code()`.
+    pub async fn user_with_sms(
+        &self,
+        mobile: impl Into<Cow<'_, str>>,
+        code: impl Into<Cow<'_, str>>,
+    ) -> Result<(Login, Cookies)> {
+        let resp: Response<_> = self
+            .acfun_id()
+            .sms_login(&SmsLoginForm::new(&mobile.into(), &code.into()))
+            .await?;
+        let login = self.check_login(resp.into_body().value()).await?;
 
-        Ok((login, cookies))
+        Ok((login, self.user_cookies(ACFUN_ID)?))
     }
 
-    pub async fn get_device_id(&self) -> Result<String> {
-        let resp: Response<_> = self.acfun_live().device_id().await?;
-        let did = resp
-            .headers()
-            .get_all(SET_COOKIE)
-            .iter()
-            .map(|v| {
-                v.to_str()
-                    .map_err(Error::HeaderToStrError)
-                    .and_then(|s| Cookie::parse(s).map_err(Error::ParseCookieError))
+    /// Gets a QR code which should be scanned and accepted in the AcFun app.
+    #[inline]
+    pub async fn start_qr_login(&self) -> Result<QrLogin> {
+        let mut qr: QrLogin = self.acfun_scan().qr_start().await?.value();
+        if qr.result == 0 {
+            qr.expires_at = Some(qr_deadline(&qr));
+            Ok(qr)
+        } else {
+            Err(Error::QrLoginFailed {
+                code: qr.result,
+                status: String::new(),
+                message: String::new(),
             })
-            .find(|c| {
-                if let Ok(c) = c {
-                    c.name() == "_did"
-                } else {
-                    false
+        }
+    }
+
+    /// Polls until the QR code is scanned, expired or cancelled.
+    ///
+    /// Request timeouts and statuses other than scanned, expired or cancelled are polled again
+    /// until `QrLogin::expires_at` passes.
+    pub async fn get_qr_scan_result(&self, qr: &QrLogin) -> Result<QrScanResult> {
+        let deadline = qr.expires_at.unwrap_or_else(|| qr_deadline(qr));
+        loop {
+            match self
+                .acfun_scan()
+                .qr_scan_result(&qr.qr_login_token, &qr.qr_login_signature)
+                .await
+            {
+                Ok(resp) => {
+                    let scan = resp.value();
+                    if scan.is_scanned() || scan.is_failed() {
+                        return Ok(scan);
+                    }
+                }
+                Err(e) if is_timeout(&e) => {}
+                Err(e) => return Err(e.into()),
+            }
+            wait_qr_poll(deadline).await?;
+        }
+    }
+
+    /// Polls until the scanned QR code is accepted, expired or cancelled, returns the cookies of the user if it is accepted.
+    ///
+    /// Request timeouts and other statuses are polled again until `QrLogin::expires_at` passes.
+    pub async fn get_qr_accept_result(
+        &self,
+        qr: &QrLogin,
+        scan: &QrScanResult,
+    ) -> Result<(QrAcceptResult, Cookies)> {
+        let deadline = qr.expires_at.unwrap_or_else(|| qr_deadline(qr));
+        loop {
+            let resp: pretend::Result<Response<_>> = self
+                .acfun_scan()
+                .qr_accept_result(&qr.qr_login_token, &scan.qr_login_signature)
+                .await;
+            match resp {
+                Ok(resp) => {
+                    let accept: QrAcceptResult = resp.into_body().value();
+                    if accept.is_accepted() || accept.is_failed() {
+                        return Ok((accept, self.user_cookies(ACFUN_SCAN)?));
+                    }
                 }
+                Err(e) if is_timeout(&e) => {}
+                Err(e) => return Err(e.into()),
+            }
+            wait_qr_poll(deadline).await?;
+        }
+    }
+
+    /// Signs in with a QR code, `show` should show the QR code to the user.
+    ///
+    /// Returns the user ID and the cookies, which can be passed to `ApiClientBuilder::cookies()`.
+    pub async fn qr_login<F>(&self, show: F) -> Result<(i64, Cookies)>
+    where
+        F: FnOnce(&QrLogin) + Send,
+    {
+        let qr = self.start_qr_login().await?;
+        show(&qr);
+        let scan = self.get_qr_scan_result(&qr).await?;
+        if !scan.is_scanned() {
+            return Err(Error::QrLoginFailed {
+                code: scan.result,
+                status: scan.status,
+                message: scan.error_msg,
+            });
+        }
+        let (accept, cookies) = self.get_qr_accept_result(&qr, &scan).await?;
+        if !accept.is_accepted() {
+            return Err(Error::QrLoginFailed {
+                code: accept.result,
+                status: accept.status,
+                message: accept.error_msg,
+            });
+        }
+
+        Ok((accept.user_id, cookies))
+    }
+
+    /// Checks the result of signing in.
+    async fn check_login(&self, login: Login) -> Result<Login> {
+        match login.result {
+            0 => Ok(login),
+            LOGIN_RESULT_WRONG_PASSWORD => Err(Error::WrongPassword),
+            LOGIN_RESULT_CAPTCHA_REQUIRED => {
+                Err(Error::CaptchaRequired(self.login_captcha(login).await?))
+            }
+            LOGIN_RESULT_WRONG_CAPTCHA => {
+                Err(Error::WrongCaptcha(self.login_captcha(login).await?))
+            }
+            LOGIN_RESULT_ACCOUNT_LOCKED => Err(Error::AccountLocked(login.error_msg)),
+            code => Err(Error::LoginFailed {
+                code,
+                message: login.error_msg,
+            }),
+        }
+    }
+
+    /// Gets a new captcha for signing in.
+    #[inline]
+    pub async fn get_captcha(&self) -> Result<Captcha> {
+        self.acfun_id().captcha().await?.value().into_result()
+    }
+
+    /// The captcha returned with the failed login, or a new one if there isn't.
+    async fn login_captcha(&self, login: Login) -> Result<Captcha> {
+        if login.key.is_empty() || login.img.is_empty() {
+            self.get_captcha().await
+        } else {
+            Ok(Captcha {
+                result: login.result,
+                key: login.key,
+                img: login.img,
             })
-            .ok_or(Error::GetDidFailed)??
-            .value()
-            .to_string();
+        }
+    }
 
-        Ok(did)
+    pub async fn get_device_id(&self) -> Result<String> {
+        let _: Response<_> = self.acfun_live().device_id().await?;
+        let device_id = self
+            .clients
+            .jar
+            .get(&Url::parse(ACFUN_LIVE)?, DEVICE_ID_COOKIE)
+            .ok_or(Error::GetDidFailed)?;
+        // the device ID may be set only for the host, but all AcFun servers need it
+        self.clients
+            .jar
+            .add_cookies(ACFUN_DOMAIN, &format!("{}={}", DEVICE_ID_COOKIE, device_id));
+
+        Ok(device_id)
     }
 
     pub async fn get_acfun_token(&self) -> Result<AcFunToken> {
-        let did: String;
-        let device_id = if self.token.device_id.is_empty() {
-            did = self.get_device_id().await?;
-            did.as_str()
+        if self.token.device_id.is_empty() {
+            let _ = self.get_device_id().await?;
+        }
+
+        if self.is_user() {
+            // the token endpoint only fails the request if the cookies aren't accepted
+            let token: UserToken = self
+                .acfun_id()
+                .user_token(TokenForm {
+                    sid: Sid::Midground,
+                })
+                .await?
+                .value()
+                .into_result()
+                .map_err(|e| match e {
+                    Error::Api { message, .. } => Error::TokenExpired(message),
+                    e => e,
+                })?;
+
+            Ok(AcFunToken::User(token))
         } else {
-            self.token.device_id.as_str()
-        };
+            let token: VisitorToken = self
+                .acfun_id()
+                .visitor_token(TokenForm { sid: Sid::Visitor })
+                .await?
+                .value()
+                .into_result()?;
 
-        match &self.token.cookies {
-            Some(c) => {
-                let token: UserToken = self
-                    .acfun_id()
-                    .user_token(
-                        TokenForm {
-                            sid: Sid::Midground,
-                        },
-                        c,
-                    )
-                    .await?
-                    .value();
+            Ok(AcFunToken::Visitor(token))
+        }
+    }
 
-                Ok(AcFunToken::User(token))
+    /// Gets a new security key and service token with the device ID and cookies in the token.
+    pub async fn refresh_token(&mut self) -> Result<()> {
+        if self.token.device_id.is_empty() {
+            self.token.device_id = self.get_device_id().await?;
+        }
+        match self.get_acfun_token().await? {
+            AcFunToken::Visitor(token) => {
+                self.token.user_id = token.user_id;
+                self.user_id_string = token.user_id.to_string();
+                self.token.security_key = token.ac_security;
+                self.token.service_token = token.acfun_api_visitor_st;
             }
-            None => {
-                let token: VisitorToken = self
-                    .acfun_id()
-                    .visitor_token(TokenForm { sid: Sid::Visitor }, device_id)
-                    .await?
-                    .value();
+            AcFunToken::User(token) => {
+                self.token.security_key = token.ssecurity;
+                self.token.service_token = token.acfun_midground_api_st;
+            }
+        }
 
-                Ok(AcFunToken::Visitor(token))
+        Ok(())
+    }
+
+    /// Checks whether the cookies of the user are still accepted by the token endpoint which
+    /// `refresh_token()` uses, returns false only if the token was expired.
+    pub async fn check_token(&self) -> Result<bool> {
+        if !self.is_user() {
+            Err(Error::NotUser)
+        } else if !self.is_login() {
+            Ok(false)
+        } else {
+            match self.get_acfun_token().await {
+                Ok(_) => Ok(true),
+                Err(Error::TokenExpired(_)) => Ok(false),
+                Err(e) => Err(e),
+            }
+        }
+    }
+
+    /// Makes the restored token usable, returns false if the client needs to sign in again.
+    ///
+    /// The service token of a user is checked first and refreshed only if it was rejected. Visitor
+    /// tokens can't be checked without a live, so they are always refreshed.
+    async fn restore(&mut self, has_account: bool) -> Result<bool> {
+        if self.token.device_id.is_empty() || (self.is_visitor() && has_account) {
+            return Ok(false);
+        }
+        if self.is_user() && self.is_login() {
+            match self.get_manager_list().await {
+                Ok(_) => return Ok(true),
+                // the service token was rejected
+                Err(Error::Api { .. }) => {}
+                Err(e) => return Err(e),
             }
         }
+        match self.refresh_token().await {
+            Ok(()) => Ok(true),
+            Err(Error::TokenExpired(_)) if has_account => Ok(false),
+            Err(e) => Err(e),
+        }
     }
 
     #[inline]
@@ -342,11 +680,22 @@ where
         } else if !self.is_login() {
             Err(Error::VisitorOrUserNotLogin)
         } else {
-            Ok(self
+            let result = self
                 .kuaishou_zt()
                 .start_play(&self.ks_query(), &StartPlayForm::new(liver_uid))
                 .await?
-                .value())
+                .value()
+                .into_result();
+            match result {
+                // the result code doesn't tell whether the live hasn't started, the live info does
+                Err(Error::Api { code, message }) => {
+                    match self.get_user_live_info(liver_uid).await {
+                        Ok(info) if info.live_data.is_none() => Err(Error::LiveNotStarted(message)),
+                        _ => Err(Error::Api { code, message }),
+                    }
+                }
+                result => result,
+            }
         }
     }
 
@@ -366,25 +715,292 @@ where
         } else if !self.is_login() {
             Err(Error::VisitorOrUserNotLogin)
         } else {
-            Ok(self
-                .kuaishou_zt()
+            self.kuaishou_zt()
                 .gift_list(&self.ks_query(), &self.ks_form(live_id.as_ref()))
                 .await?
-                .value())
+                .value()
+                .into_result()
+        }
+    }
+
+    #[inline]
+    pub async fn send_comment(
+        &self,
+        live_id: impl Into<Cow<'_, str>>,
+        content: impl Into<Cow<'_, str>>,
+    ) -> Result<ActionResult> {
+        let live_id = live_id.into();
+        let content = content.into();
+        if live_id.is_empty() {
+            Err(Error::EmptyLiveId)
+        } else if content.is_empty() {
+            Err(Error::EmptyComment)
+        } else if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            self.kuaishou_zt()
+                .comment(
+                    &self.ks_query(),
+                    &CommentForm::new(self.token.user_id, live_id.as_ref(), content.as_ref()),
+                )
+                .await?
+                .value()
+                .into_result()
+        }
+    }
+
+    #[inline]
+    pub async fn send_like(
+        &self,
+        live_id: impl Into<Cow<'_, str>>,
+        count: u32,
+    ) -> Result<ActionResult> {
+        let live_id = live_id.into();
+        if live_id.is_empty() {
+            Err(Error::EmptyLiveId)
+        } else if count == 0 {
+            Err(Error::ZeroLikeCount)
+        } else if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            self.kuaishou_zt()
+                .like(
+                    &self.ks_query(),
+                    &LikeForm::new(self.token.user_id, live_id.as_ref(), count),
+                )
+                .await?
+                .value()
+                .into_result()
+        }
+    }
+
+    pub async fn send_gift(
+        &self,
+        live_id: impl Into<Cow<'_, str>>,
+        gift: &Gift,
+        batch_size: i32,
+    ) -> Result<SendGift> {
+        let live_id = live_id.into();
+        if live_id.is_empty() {
+            Err(Error::EmptyLiveId)
+        } else if !gift.allow_batch_send_size_list.contains(&batch_size) {
+            Err(Error::InvalidGiftBatchSize(gift.gift_id, batch_size))
+        } else if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            let combo_key = format!(
+                "{}_{}_{}",
+                self.token.user_id,
+                gift.gift_id,
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis()
+            );
+            self.kuaishou_zt()
+                .send_gift(
+                    &self.ks_query(),
+                    &GiftForm::new(
+                        self.token.user_id,
+                        live_id.as_ref(),
+                        gift.gift_id,
+                        batch_size,
+                        &combo_key,
+                    ),
+                )
+                .await?
+                .value()
+                .into_result()
+        }
+    }
+
+    #[inline]
+    pub async fn get_manager_list(&self) -> Result<ManagerList> {
+        if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            self.kuaishou_zt()
+                .manager_list(&self.ks_query(), &self.ks_form(""))
+                .await?
+                .value()
+                .into_result()
+        }
+    }
+
+    #[inline]
+    pub async fn add_manager(&self, manager_uid: i64) -> Result<ActionResult> {
+        if manager_uid <= 0 {
+            Err(Error::InvalidUid(manager_uid))
+        } else if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            self.kuaishou_zt()
+                .add_manager(
+                    &self.ks_query(),
+                    &ManagerForm::new(self.token.user_id, manager_uid),
+                )
+                .await?
+                .value()
+                .into_result()
+        }
+    }
+
+    #[inline]
+    pub async fn delete_manager(&self, manager_uid: i64) -> Result<ActionResult> {
+        if manager_uid <= 0 {
+            Err(Error::InvalidUid(manager_uid))
+        } else if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            self.kuaishou_zt()
+                .delete_manager(
+                    &self.ks_query(),
+                    &ManagerForm::new(self.token.user_id, manager_uid),
+                )
+                .await?
+                .value()
+                .into_result()
+        }
+    }
+
+    /// Kicks the user out of the live room, `as_author` should be true if the current user is the liver.
+    pub async fn kick_user(
+        &self,
+        live_id: impl Into<Cow<'_, str>>,
+        user_id: i64,
+        as_author: bool,
+    ) -> Result<ActionResult> {
+        let live_id = live_id.into();
+        if live_id.is_empty() {
+            Err(Error::EmptyLiveId)
+        } else if user_id <= 0 {
+            Err(Error::InvalidUid(user_id))
+        } else if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            let form = KickForm::new(self.token.user_id, live_id.as_ref(), user_id);
+            let resp = if as_author {
+                self.kuaishou_zt()
+                    .author_kick(&self.ks_query(), &form)
+                    .await?
+            } else {
+                self.kuaishou_zt()
+                    .manager_kick(&self.ks_query(), &form)
+                    .await?
+            };
+
+            resp.value().into_result()
+        }
+    }
+
+    #[inline]
+    pub async fn mute_user(
+        &self,
+        live_id: impl Into<Cow<'_, str>>,
+        user_id: i64,
+        duration: Duration,
+    ) -> Result<ActionResult> {
+        let live_id = live_id.into();
+        if live_id.is_empty() {
+            Err(Error::EmptyLiveId)
+        } else if user_id <= 0 {
+            Err(Error::InvalidUid(user_id))
+        } else if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            self.kuaishou_zt()
+                .mute(
+                    &self.ks_query(),
+                    &MuteForm::new(
+                        self.token.user_id,
+                        live_id.as_ref(),
+                        user_id,
+                        i64::try_from(duration.as_millis()).unwrap_or(i64::MAX),
+                    ),
+                )
+                .await?
+                .value()
+                .into_result()
+        }
+    }
+
+    #[inline]
+    pub async fn block_user(&self, user_id: i64) -> Result<ActionResult> {
+        if user_id <= 0 {
+            Err(Error::InvalidUid(user_id))
+        } else if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            self.kuaishou_zt()
+                .block(
+                    &self.ks_query(),
+                    &ManagerForm::new(self.token.user_id, user_id),
+                )
+                .await?
+                .value()
+                .into_result()
+        }
+    }
+
+    #[inline]
+    pub async fn unblock_user(&self, user_id: i64) -> Result<ActionResult> {
+        if user_id <= 0 {
+            Err(Error::InvalidUid(user_id))
+        } else if !self.is_user() {
+            Err(Error::NotUser)
+        } else {
+            self.kuaishou_zt()
+                .unblock(
+                    &self.ks_query(),
+                    &ManagerForm::new(self.token.user_id, user_id),
+                )
+                .await?
+                .value()
+                .into_result()
         }
     }
 
     #[inline]
     pub async fn get_live_list(&self, count: u32, page: u32) -> Result<LiveList> {
-        Ok(self
-            .acfun_live()
-            .live_list(
+        self.acfun_live()
+            .live_list(count, page)
+            .await?
+            .value()
+            .into_result()
+    }
+
+    /// Gets a page of the live list, `pcursor` is empty for the first page or the `pcursor` of the
+    /// last page, which is `"no_more"` if it was the last page.
+    ///
+    /// Only the lives matching one of the display filters are returned if `filters` isn't empty.
+    pub async fn get_live_list_page(
+        &self,
+        count: u32,
+        pcursor: &str,
+        filters: &[DisplayFilter],
+    ) -> Result<LiveList> {
+        let filters = if filters.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(
+                &filters
+                    .iter()
+                    .map(|f| FilterQuery {
+                        filter_type: f.filter_type,
+                        filter_id: f.filter_id,
+                    })
+                    .collect::<Vec<_>>(),
+            )?)
+        };
+        self.acfun_live()
+            .live_list_page(&LiveListQuery {
                 count,
-                page,
-                self.token.cookies.as_deref().unwrap_or_default(),
-            )
+                pcursor,
+                filters,
+            })
             .await?
-            .value())
+            .value()
+            .into_result()
     }
 
     #[inline]
@@ -392,11 +1008,7 @@ where
         if !self.is_user() {
             Err(Error::NotUser)
         } else {
-            Ok(self
-                .acfun_live()
-                .medal_list(self.token.cookies.as_deref().unwrap_or_default())
-                .await?
-                .value())
+            self.acfun_live().medal_list().await?.value().into_result()
         }
     }
 
@@ -405,11 +1017,11 @@ where
         if liver_uid <= 0 {
             Err(Error::InvalidUid(liver_uid))
         } else {
-            Ok(self
-                .acfun_live()
-                .live_info(liver_uid, self.token.cookies.as_deref().unwrap_or_default())
+            self.acfun_live()
+                .live_info(liver_uid)
                 .await?
-                .value())
+                .value()
+                .into_result()
         }
     }
 
@@ -421,11 +1033,11 @@ where
         } else if !self.is_login() {
             Err(Error::VisitorOrUserNotLogin)
         } else {
-            Ok(self
-                .kuaishou_zt()
+            self.kuaishou_zt()
                 .end_summary(&self.ks_query(), &self.ks_form(live_id.as_ref()))
                 .await?
-                .value())
+                .value()
+                .into_result()
         }
     }
 
@@ -434,11 +1046,11 @@ where
         if liver_uid <= 0 {
             Err(Error::InvalidUid(liver_uid))
         } else {
-            Ok(self
-                .acfun_live()
-                .medal_rank_list(liver_uid, self.token.cookies.as_deref().unwrap_or_default())
+            self.acfun_live()
+                .medal_rank_list(liver_uid)
                 .await?
-                .value())
+                .value()
+                .into_result()
         }
     }
 }
@@ -451,7 +1063,12 @@ pub struct ApiClientBuilder<C> {
     client: ApiClient<C>,
     account: Option<String>,
     password: Option<String>,
+    captcha: Option<(String, String)>,
+    sms: Option<(String, String)>,
+    cookies: Option<(i64, Cookies)>,
     liver_uid: Option<i64>,
+    token: Option<ApiToken>,
+    store: Option<Arc<dyn TokenStore>>,
 }
 
 #[cfg(feature = "default_http_client")]
@@ -462,7 +1079,12 @@ impl ApiClientBuilder<HttpClient> {
             client: ApiClient::default_client()?,
             account: None,
             password: None,
+            captcha: None,
+            sms: None,
+            cookies: None,
             liver_uid: None,
+            token: None,
+            store: None,
         })
     }
 }
@@ -477,7 +1099,12 @@ impl<C> ApiClientBuilder<C> {
             client: ApiClient::new(client)?,
             account: None,
             password: None,
+            captcha: None,
+            sms: None,
+            cookies: None,
             liver_uid: None,
+            token: None,
+            store: None,
         })
     }
 
@@ -493,6 +1120,77 @@ impl<C> ApiClientBuilder<C> {
         self
     }
 
+    /// Signs in with the key and the solved captcha from `Error::CaptchaRequired` or `Error::WrongCaptcha`.
+    #[inline]
+    pub fn captcha<'a>(
+        mut self,
+        key: impl Into<Cow<'a, str>>,
+        captcha: impl Into<Cow<'a, str>>,
+    ) -> Self {
+        self.captcha = Some((key.into().into_owned(), captcha.into().into_owned()));
+
+        self
+    }
+
+    /// Signs in with the mobile phone and the SMS code sent by `ApiClient::send_sms_code()`.
+    #[inline]
+    pub fn sms<'a>(
+        mut self,
+        mobile: impl Into<Cow<'a, str>>,
+        code: impl Into<Cow<'a, str>>,
+    ) -> Self {
+        self.sms = Some((mobile.into().into_owned(), code.into().into_owned()));
+
+        self
+    }
+
+    /// Uses the cookies of a user who has signed in, e.g. the result of `ApiClient::qr_login()`.
+    #[inline]
+    pub fn cookies(mut self, user_id: i64, cookies: impl Into<Cookies>) -> Self {
+        self.cookies = Some((user_id, cookies.into()));
+
+        self
+    }
+
+    /// Restores a saved token instead of signing in.
+    ///
+    /// The service token of a user is refreshed only if it was rejected, a visitor token is always
+    /// refreshed. If the cookies of the user were also expired, the client signs in with the account
+    /// and password set by `user()`. Other errors are returned instead of signing in again.
+    #[inline]
+    pub fn token(mut self, token: ApiToken) -> Self {
+        self.token = Some(token);
+
+        self
+    }
+
+    /// Restores the token from the store if `token()` isn't set, and saves the token after building.
+    #[inline]
+    pub fn token_store(mut self, store: Arc<dyn TokenStore>) -> Self {
+        self.store = Some(store);
+
+        self
+    }
+
+    /// Retries idempotent requests with the policy, including the requests when building.
+    #[inline]
+    pub fn retry_policy(mut self, policy: Option<RetryPolicy>) -> Self {
+        let _ = self.client.set_retry_policy(policy);
+
+        self
+    }
+
+    /// Sets the minimum interval between requests to the host, e.g. `api.kuaishouzt.com`.
+    #[inline]
+    pub fn rate_limit(self, host: impl Into<String>, interval: Duration) -> Self {
+        let _ = self
+            .client
+            .rate_limiter()
+            .set_interval(host, Some(interval));
+
+        self
+    }
+
     #[inline]
     pub const fn liver_uid(mut self, liver_uid: i64) -> Self {
         self.liver_uid = Some(liver_uid);
@@ -507,24 +1205,42 @@ where
 {
     pub async fn build(self) -> Result<ApiClient<C>> {
         let mut client = self.client;
-        if let Some((account, password)) = self.account.zip(self.password) {
-            let (login, cookies) = client.user(account, password).await?;
-            client.token.user_id = login.user_id;
-            client.user_id_string = login.user_id.to_string();
-            client.token.cookies = Some(cookies);
-        }
-        client.token.device_id = client.get_device_id().await?;
-        match client.get_acfun_token().await? {
-            AcFunToken::Visitor(token) => {
-                client.token.user_id = token.user_id;
-                client.user_id_string = token.user_id.to_string();
-                client.token.security_key = token.ac_security;
-                client.token.service_token = token.acfun_api_visitor_st;
+        let token = match (self.token, &self.store) {
+            (Some(token), _) => Some(token),
+            (None, Some(store)) => store.load().await?,
+            (None, None) => None,
+        };
+        let restored = match token {
+            Some(token) => {
+                let _ = client.set_token(token);
+                client
+                    .restore(self.account.is_some() || self.sms.is_some() || self.cookies.is_some())
+                    .await?
             }
-            AcFunToken::User(token) => {
-                client.token.security_key = token.ssecurity;
-                client.token.service_token = token.acfun_midground_api_st;
+            None => false,
+        };
+        if !restored {
+            let _ = client.set_token(ApiToken::default());
+            let user = if let Some((account, password)) = self.account.zip(self.password) {
+                let (key, captcha) = self.captcha.unwrap_or_default();
+                let (login, cookies) = client
+                    .user_with_captcha(account, password, key, captcha)
+                    .await?;
+                Some((login.user_id, cookies))
+            } else if let Some((mobile, code)) = self.sms {
+                let (login, cookies) = client.user_with_sms(mobile, code).await?;
+                Some((login.user_id, cookies))
+            } else {
+                self.cookies
+            };
+            if let Some((user_id, cookies)) = user {
+                client.clients.jar.add_cookies(ACFUN_DOMAIN, &cookies);
+                client.token.user_id = user_id;
+                client.user_id_string = user_id.to_string();
+                client.token.cookies = Some(cookies);
             }
+            client.token.device_id = client.get_device_id().await?;
+            client.refresh_token().await?;
         }
         if let Some(liver_uid) = self.liver_uid {
             let info = client.get_live_info(liver_uid).await?;
@@ -536,37 +1252,531 @@ where
                 title: info.data.caption,
                 start_time: info.data.live_start_time,
                 panoramic: info.data.panoramic,
-                stream_name: info.data.video_play_res.stream_name,
+                stream_name: info.data.video_play_res.stream_name.clone(),
                 stream_list: info
                     .data
                     .video_play_res
                     .live_adaptive_manifest
-                    .into_iter()
-                    .next()
+                    .first()
                     .ok_or(Error::IndexOutOfRange("live_adaptive_manifest", 0))?
                     .adaptation_set
                     .representation
-                    .into_iter()
-                    .map(|r| Stream {
-                        url: r.url,
-                        bitrate: r.bitrate,
-                        quality_type: r.quality_type,
-                        quality_name: r.name,
-                    })
+                    .iter()
+                    .map(Stream::from)
                     .collect(),
+                stream_info: info.data.video_play_res,
             });
         }
 
+        if let Some(store) = &self.store {
+            store.save(client.token()).await?;
+        }
+
         Ok(client)
     }
 }
 
-#[cfg(feature = "default_http_client")]
+/// Returns when a QR code fetched now expires, `QrLogin::expire_time` is in milliseconds.
+#[inline]
+fn qr_deadline(qr: &QrLogin) -> Instant {
+    Instant::now() + Duration::from_millis(u64::try_from(qr.expire_time).unwrap_or_default())
+}
+
+/// Waits before polling the QR code again, fails if it will be expired by then.
+async fn wait_qr_poll(deadline: Instant) -> Result<()> {
+    if Instant::now() + QR_POLL_INTERVAL >= deadline {
+        return Err(Error::QrLoginFailed {
+            code: 0,
+            status: "EXPIRED".to_string(),
+            message: "the QR code expired".to_string(),
+        });
+    }
+    Delay::new(QR_POLL_INTERVAL).await;
+
+    Ok(())
+}
+
+/// Long polling requests may time out before the server responds.
+#[inline]
+fn is_timeout(e: &pretend::Error) -> bool {
+    matches!(e, pretend::Error::Response(_) | pretend::Error::Body(_))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{author::LiveSettings, mock::*, store::MemoryTokenStore};
+    use pretend::{
+        client::Method,
+        http::header::{COOKIE, SET_COOKIE},
+        StatusCode,
+    };
     use std::env;
 
+    #[tokio::test]
+    async fn test_mock_visitor() -> Result<()> {
+        let mock = MockClient::new();
+        let client = ApiClientBuilder::new(mock.clone())?
+            .liver_uid(MOCK_LIVER_UID)
+            .build()
+            .await?;
+        assert!(client.is_login());
+        assert!(client.is_visitor());
+        assert_eq!(client.user_id(), MOCK_VISITOR_ID);
+        assert_eq!(client.token().device_id, MOCK_DEVICE_ID);
+        assert_eq!(client.live_id(), Some(MOCK_LIVE_ID));
+        let live = client.live().expect("no live info");
+        assert_eq!(live.tickets.len(), 2);
+        assert_eq!(live.stream_name, "stream_1");
+        assert_eq!(live.stream_list.len(), 4);
+        assert_eq!(live.stream_info.live_adaptive_manifest.len(), 2);
+        assert_eq!(
+            live.select_stream(&StreamSelector::new().free_traffic_cdn(true))
+                .map(|s| s.url)
+                .as_deref(),
+            Some("https://free.mock.cdn/live/stream_1_high.flv")
+        );
+
+        let gifts: GiftList = client.get().await?;
+        assert_eq!(gifts.data.gift_list.len(), 2);
+        let live_list: LiveList = client.get().await?;
+        assert_eq!(live_list.live_list.len(), 3);
+        assert_eq!(live_list.live_types().len(), 3);
+        let info: UserLiveInfo = client.get().await?;
+        assert_eq!(info.author_id, MOCK_LIVER_UID);
+        let summary: Summary = client.get().await?;
+        assert_eq!(summary.data.watch_count, "100");
+        let medal_rank_list: MedalRankList = client.get().await?;
+        assert_eq!(medal_rank_list.friendship_degree_rank.len(), 1);
+        assert!(matches!(client.get_medal_list().await, Err(Error::NotUser)));
+        assert!(matches!(
+            client.send_comment(MOCK_LIVE_ID, "comment").await,
+            Err(Error::NotUser)
+        ));
+
+        let requests = mock.requests();
+        assert_eq!(requests[0].method, Method::GET);
+        assert_eq!(requests[0].url.as_str(), ACFUN_LIVE);
+        let start_play = requests
+            .iter()
+            .find(|r| r.url.path() == "/rest/zt/live/web/startPlay")
+            .expect("no startPlay request");
+        assert!(start_play
+            .url
+            .query()
+            .unwrap_or_default()
+            .contains("acfun.api.visitor_st"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_user() -> Result<()> {
+        let mock = MockClient::new();
+        let client = ApiClientBuilder::new(mock.clone())?
+            .user("account", "password")
+            .liver_uid(MOCK_LIVER_UID)
+            .build()
+            .await?;
+        assert!(client.is_login());
+        assert!(client.is_user());
+        assert_eq!(client.user_id(), MOCK_USER_ID);
+        assert_eq!(
+            client.token().cookies.as_deref(),
+            Some("acPasssToken=mock_pass_token; auth_key=10000; safety_id=mock_safety_id")
+        );
+        let token_request = mock
+            .requests()
+            .into_iter()
+            .find(|r| r.url.path() == "/rest/web/token/get")
+            .expect("no user token request");
+        let cookie = token_request
+            .headers
+            .get(COOKIE)
+            .expect("no cookies in the user token request")
+            .to_str()
+            .expect("invalid cookies");
+        assert!(cookie.contains("_did=web_mock_device_id"));
+        assert!(cookie.contains(client.token().cookies.as_deref().unwrap_or_default()));
+        assert_eq!(client.get_device_id().await?, "web_mock_device_id");
+        // clones don't share cookies
+        let live_url = Url::parse(ACFUN_LIVE)?;
+        let mut other = client.clone();
+        let _ = other.set_token(ApiToken::default());
+        assert_eq!(other.cookie_jar().cookies(&live_url), None);
+        assert_eq!(
+            client.cookie_jar().get(&live_url, "auth_key").as_deref(),
+            Some("10000")
+        );
+
+        let gifts: GiftList = client.get().await?;
+        let _live_list: LiveList = client.get().await?;
+        let medal_list: MedalList = client.get().await?;
+        assert_eq!(medal_list.medal_list.len(), 1);
+        let _info: UserLiveInfo = client.get().await?;
+        let _summary: Summary = client.get().await?;
+        let _medal_rank_list: MedalRankList = client.get().await?;
+
+        assert_eq!(
+            client.send_comment(MOCK_LIVE_ID, "comment").await?.result,
+            1
+        );
+        assert_eq!(client.send_like(MOCK_LIVE_ID, 10).await?.result, 1);
+        let gift = &gifts.data.gift_list[0];
+        assert_eq!(
+            client
+                .send_gift(MOCK_LIVE_ID, gift, 5)
+                .await?
+                .data
+                .combo_key,
+            "mock_combo_key"
+        );
+        assert!(matches!(
+            client.send_gift(MOCK_LIVE_ID, gift, 2).await,
+            Err(Error::InvalidGiftBatchSize(1, 2))
+        ));
+        assert_eq!(client.get_manager_list().await?.data.list.len(), 1);
+        let _ = client.add_manager(20000).await?;
+        let _ = client.delete_manager(20000).await?;
+        let _ = client.kick_user(MOCK_LIVE_ID, 20000, true).await?;
+        let _ = client.kick_user(MOCK_LIVE_ID, 20000, false).await?;
+        let _ = client
+            .mute_user(MOCK_LIVE_ID, 20000, Duration::from_secs(60))
+            .await?;
+        let _ = client.block_user(20000).await?;
+        let _ = client.unblock_user(20000).await?;
+
+        let config = client.get_push_config().await?;
+        let status = client.get_author_live_status().await?;
+        assert_eq!(status.data.live_id, MOCK_LIVE_ID);
+        let live = client
+            .start_live(config.data.stream_name, &LiveSettings::new("mock live"))
+            .await?;
+        let _ = client
+            .change_title_and_cover(live.data.live_id.as_str(), "new title", "")
+            .await?;
+        let _ = client.stop_live(live.data.live_id).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_api_error() -> Result<()> {
+        let mock = MockClient::new();
+        let client = ApiClientBuilder::new(mock.clone())?.build().await?;
+        let _ = mock.json(
+            Method::POST,
+            "/rest/zt/live/web/startPlay",
+            LIVE_NOT_STARTED,
+        );
+        // the liver is still living
+        assert!(matches!(
+            client.get_live_info(MOCK_LIVER_UID).await,
+            Err(Error::Api { code: 380_023, .. })
+        ));
+        let _ = mock.json(Method::GET, "/api/live/info", USER_LIVE_OFFLINE);
+        assert!(matches!(
+            client.get_live_info(MOCK_LIVER_UID).await,
+            Err(Error::LiveNotStarted(message)) if message == "live not started"
+        ));
+        // the result codes of other endpoints aren't mapped
+        let _ = mock.json(Method::POST, "/rest/zt/live/web/gift/list", TOKEN_EXPIRED);
+        assert!(matches!(
+            client.get_gift_list(MOCK_LIVE_ID).await,
+            Err(Error::Api { code: 112, .. })
+        ));
+        let _ = mock.json(
+            Method::GET,
+            "/api/live/info",
+            r#"{"result": 10, "error_msg": "unknown error"}"#,
+        );
+        assert!(matches!(
+            client.get_user_live_info(MOCK_LIVER_UID).await,
+            Err(Error::Api { code: 10, message }) if message == "unknown error"
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_restore() -> Result<()> {
+        let paths = |mock: &MockClient| -> Vec<String> {
+            let paths = mock
+                .requests()
+                .into_iter()
+                .map(|r| r.url.path().to_string())
+                .collect();
+            mock.clear_requests();
+            paths
+        };
+        let mock = MockClient::new();
+        let store = MemoryTokenStore::new();
+        let client = ApiClientBuilder::new(mock.clone())?
+            .user("account", "password")
+            .token_store(Arc::new(store.clone()))
+            .build()
+            .await?;
+        assert_eq!(store.token().as_ref(), Some(client.token()));
+        assert!(paths(&mock).contains(&"/rest/web/login/signin".to_string()));
+
+        // the saved token is still valid
+        let restored = ApiClientBuilder::new(mock.clone())?
+            .user("account", "password")
+            .token_store(Arc::new(store.clone()))
+            .build()
+            .await?;
+        assert_eq!(restored.token(), client.token());
+        assert_eq!(
+            paths(&mock),
+            vec!["/rest/zt/live/web/author/action/manager/list"]
+        );
+        assert!(restored.check_token().await?);
+
+        // the service token was rejected
+        let service_token_rejected = || {
+            let _ = mock.route_once(
+                Method::POST,
+                "/rest/zt/live/web/author/action/manager/list",
+                MockResponse::json(r#"{"result": 10, "error_msg": "rejected"}"#),
+            );
+        };
+        mock.clear_requests();
+        service_token_rejected();
+        let refreshed = ApiClientBuilder::new(mock.clone())?
+            .user("account", "password")
+            .token(client.token().clone())
+            .build()
+            .await?;
+        assert_eq!(refreshed.token(), client.token());
+        assert_eq!(
+            paths(&mock),
+            vec![
+                "/rest/zt/live/web/author/action/manager/list",
+                "/rest/web/token/get"
+            ]
+        );
+
+        // the cookies were expired
+        let token_expired = || {
+            service_token_rejected();
+            let _ = mock.route_once(
+                Method::POST,
+                "/rest/web/token/get",
+                MockResponse::json(TOKEN_EXPIRED),
+            );
+        };
+        token_expired();
+        assert!(!restored.check_token().await?);
+        mock.clear_requests();
+        token_expired();
+        assert!(matches!(
+            ApiClientBuilder::new(mock.clone())?
+                .token(client.token().clone())
+                .build()
+                .await,
+            Err(Error::TokenExpired(_))
+        ));
+        let _ = paths(&mock);
+        token_expired();
+        let relogin = ApiClientBuilder::new(mock.clone())?
+            .user("account", "password")
+            .token(client.token().clone())
+            .build()
+            .await?;
+        assert!(relogin.is_user());
+        assert_eq!(relogin.token(), client.token());
+        assert!(paths(&mock).contains(&"/rest/web/login/signin".to_string()));
+
+        // other errors of the token endpoint aren't treated as an expired token
+        let _ = mock.route_once(
+            Method::POST,
+            "/rest/web/token/get",
+            MockResponse::new(StatusCode::SERVICE_UNAVAILABLE),
+        );
+        assert!(restored.check_token().await.is_err());
+        service_token_rejected();
+        let _ = mock.route_once(
+            Method::POST,
+            "/rest/web/token/get",
+            MockResponse::new(StatusCode::SERVICE_UNAVAILABLE),
+        );
+        assert!(ApiClientBuilder::new(mock.clone())?
+            .user("account", "password")
+            .token(client.token().clone())
+            .build()
+            .await
+            .is_err());
+        assert!(!paths(&mock).contains(&"/rest/web/login/signin".to_string()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_login_error() -> Result<()> {
+        let mock = MockClient::new();
+        let build = || ApiClientBuilder::new(mock.clone()).map(|b| b.user("account", "password"));
+        let _ = mock.json(Method::POST, "/rest/web/login/signin", LOGIN_WRONG_PASSWORD);
+        assert!(matches!(build()?.build().await, Err(Error::WrongPassword)));
+        let _ = mock.json(Method::POST, "/rest/web/login/signin", LOGIN_ACCOUNT_LOCKED);
+        assert!(matches!(
+            build()?.build().await,
+            Err(Error::AccountLocked(_))
+        ));
+
+        let _ = mock.json(
+            Method::POST,
+            "/rest/web/login/signin",
+            LOGIN_CAPTCHA_REQUIRED,
+        );
+        let captcha = match build()?.build().await {
+            Err(Error::CaptchaRequired(captcha)) => captcha,
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        };
+        assert_eq!(captcha.key, "mock_login_captcha_key");
+        assert!(!captcha.img.is_empty());
+        let client = ApiClientBuilder::new(mock.clone())?;
+        assert_eq!(client.client.get_captcha().await?.key, "mock_captcha_key");
+
+        let _ = mock.route(
+            Method::POST,
+            "/rest/web/login/signin",
+            MockResponse::json(LOGIN).header(SET_COOKIE, "auth_key=10000; Path=/"),
+        );
+        mock.clear_requests();
+        let client = build()?.captcha(captcha.key, "abcd").build().await?;
+        assert!(client.is_user());
+        let login = mock
+            .requests()
+            .into_iter()
+            .find(|r| r.url.path() == "/rest/web/login/signin")
+            .expect("no login request");
+        assert_eq!(
+            login.body.as_deref(),
+            Some(
+                &b"username=account&password=password&key=mock_login_captcha_key&captcha=abcd"[..]
+            )
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_sms_and_qr_login() -> Result<()> {
+        let cookies = "acPasssToken=mock_pass_token; auth_key=10000; safety_id=mock_safety_id";
+        let mock = MockClient::new();
+        let visitor = ApiClientBuilder::new(mock.clone())?.build().await?;
+        visitor.send_sms_code("13800000000").await?;
+        let client = ApiClientBuilder::new(mock.clone())?
+            .sms("13800000000", "123456")
+            .build()
+            .await?;
+        assert!(client.is_login());
+        assert_eq!(client.user_id(), MOCK_USER_ID);
+        assert_eq!(client.token().cookies.as_deref(), Some(cookies));
+
+        let mut shown = None;
+        let (user_id, qr_cookies) = visitor
+            .qr_login(|qr| shown = Some(qr.qr_login_token.clone()))
+            .await?;
+        assert_eq!(shown.as_deref(), Some("mock_qr_token"));
+        assert_eq!(qr_cookies, cookies);
+        let accept = mock
+            .requests()
+            .into_iter()
+            .find(|r| r.url.path() == "/rest/pc-direct/qr/acceptResult")
+            .expect("no QR accept request");
+        assert_eq!(accept.url.host_str(), Some("scan.acfun.cn"));
+        assert_eq!(
+            accept.url.query(),
+            Some("qrLoginToken=mock_qr_token&qrLoginSignature=mock_qr_scanned_signature")
+        );
+        let qr_client = ApiClientBuilder::new(mock.clone())?
+            .cookies(user_id, qr_cookies)
+            .build()
+            .await?;
+        assert_eq!(qr_client.token(), client.token());
+
+        let _ = mock.json(
+            Method::GET,
+            "/rest/pc-direct/qr/scanResult",
+            r#"{"result": 0, "status": "CANCELLED"}"#,
+        );
+        assert!(matches!(
+            visitor.qr_login(|_| {}).await,
+            Err(Error::QrLoginFailed { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_qr_polling() -> Result<()> {
+        let mock = MockClient::new();
+        let visitor = ApiClientBuilder::new(mock.clone())?.build().await?;
+        let _ = mock.json(
+            Method::GET,
+            "/rest/pc-direct/qr/scanResult",
+            r#"{"result": 0, "status": "PENDING"}"#,
+        );
+        mock.clear_requests();
+        let scanned = async {
+            futures_timer::Delay::new(Duration::from_millis(100)).await;
+            let _ = mock.json(Method::GET, "/rest/pc-direct/qr/scanResult", QR_SCAN_RESULT);
+        };
+        let (login, ()) = futures::join!(visitor.qr_login(|_| {}), scanned);
+        assert_eq!(login?.0, MOCK_USER_ID);
+        let polls = mock
+            .requests()
+            .into_iter()
+            .filter(|r| r.url.path() == "/rest/pc-direct/qr/scanResult")
+            .count();
+        assert_eq!(polls, 2);
+
+        // the accept phase stops at the deadline of the QR code instead of starting a new one
+        let _ = mock.json(
+            Method::GET,
+            "/rest/pc-direct/qr/acceptResult",
+            r#"{"result": 0, "status": "PENDING"}"#,
+        );
+        let mut qr = visitor.start_qr_login().await?;
+        assert!(qr.expires_at.is_some());
+        qr.expires_at = Some(Instant::now());
+        let scan = visitor.get_qr_scan_result(&qr).await?;
+        assert!(matches!(
+            visitor.get_qr_accept_result(&qr, &scan).await,
+            Err(Error::QrLoginFailed { status, .. }) if status == "EXPIRED"
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_route() -> Result<()> {
+        let mock = MockClient::new();
+        let _ = mock.route(
+            Method::POST,
+            "/rest/zt/live/web/gift/list",
+            MockResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+        );
+        let client = ApiClientBuilder::new(mock)?
+            .liver_uid(MOCK_LIVER_UID)
+            .build()
+            .await?;
+        assert!(matches!(
+            client.get::<GiftList>().await,
+            Err(Error::PretendError(pretend::Error::Status(
+                StatusCode::INTERNAL_SERVER_ERROR
+            )))
+        ));
+
+        let client = ApiClientBuilder::new(MockClient::empty())?;
+        assert!(matches!(client.build().await, Err(Error::GetDidFailed)));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "default_http_client")]
+    #[ignore = "needs the network and the LIVER_UID environment variable"]
     #[tokio::test]
     async fn test_visitor() -> Result<()> {
         let liver_uid: i64 = env::var("LIVER_UID")
@@ -587,6 +1797,8 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "default_http_client")]
+    #[ignore = "needs the network and the ACCOUNT, PASSWORD and LIVER_UID environment variables"]
     #[tokio::test]
     async fn test_user() -> Result<()> {
         let account = env::var("ACCOUNT")
@@ -0,0 +1,311 @@
+//! Tracks the live list and reports the changes of the lives between snapshots.
+
+use crate::{client::ApiClient, live_list::LiveListFilter, response::*, Result};
+use futures::TryStreamExt;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DirectoryEvent {
+    /// The live appeared in the live list, lives in the first snapshot are also reported.
+    LiveStarted(Box<UserLiveInfo>),
+    /// The live disappeared from the live list, with its last info.
+    LiveEnded(Box<UserLiveInfo>),
+    TitleChanged {
+        liver_uid: i64,
+        live_id: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    CategoryChanged {
+        liver_uid: i64,
+        live_id: String,
+        old: Option<LiveType>,
+        new: Option<LiveType>,
+    },
+    /// The online count rose to or above the threshold, or fell below it.
+    OnlineCountCrossed {
+        liver_uid: i64,
+        live_id: String,
+        threshold: i32,
+        online_count: i32,
+        rising: bool,
+    },
+}
+
+/// Keeps the last snapshot of the live list keyed by the liver's uid and diffs it with the next one.
+#[derive(Clone, Debug, Default)]
+pub struct DirectoryTracker {
+    lives: HashMap<i64, UserLiveInfo>,
+    thresholds: Vec<i32>,
+}
+
+impl DirectoryTracker {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reports `DirectoryEvent::OnlineCountCrossed` when the online count of a live crosses the threshold.
+    #[inline]
+    pub fn threshold(mut self, online_count: i32) -> Self {
+        self.thresholds.push(online_count);
+        self
+    }
+
+    /// The lives in the last snapshot.
+    #[inline]
+    pub fn lives(&self) -> impl Iterator<Item = &UserLiveInfo> {
+        self.lives.values()
+    }
+
+    #[inline]
+    pub fn live(&self, liver_uid: i64) -> Option<&UserLiveInfo> {
+        self.lives.get(&liver_uid)
+    }
+
+    fn diff(&self, old: &UserLiveInfo, new: &UserLiveInfo, events: &mut Vec<DirectoryEvent>) {
+        let (old_data, new_data) = match (old.live_data.as_ref(), new.live_data.as_ref()) {
+            (Some(old), Some(new)) => (old, new),
+            _ => return,
+        };
+        let liver_uid = new.author_id;
+        let live_id = &new_data.live_id;
+        if old_data.title != new_data.title {
+            events.push(DirectoryEvent::TitleChanged {
+                liver_uid,
+                live_id: live_id.clone(),
+                old: old_data.title.clone(),
+                new: new_data.title.clone(),
+            });
+        }
+        if old_data.live_type.as_ref().map(|t| t.id) != new_data.live_type.as_ref().map(|t| t.id) {
+            events.push(DirectoryEvent::CategoryChanged {
+                liver_uid,
+                live_id: live_id.clone(),
+                old: old_data.live_type.clone(),
+                new: new_data.live_type.clone(),
+            });
+        }
+        let (old_count, new_count) = (old_data.online_count, new_data.online_count);
+        for &threshold in &self.thresholds {
+            let rising = old_count < threshold && new_count >= threshold;
+            if rising || (old_count >= threshold && new_count < threshold) {
+                events.push(DirectoryEvent::OnlineCountCrossed {
+                    liver_uid,
+                    live_id: live_id.clone(),
+                    threshold,
+                    online_count: new_count,
+                    rising,
+                });
+            }
+        }
+    }
+
+    /// Replaces the snapshot with the lives and returns the changes.
+    ///
+    /// A liver starting another live is reported as the old live ended and the new one started.
+    /// Only the first live of a liver is kept if the liver appears more than once.
+    #[inline]
+    pub fn update(&mut self, lives: impl IntoIterator<Item = UserLiveInfo>) -> Vec<DirectoryEvent> {
+        self.update_filtered(lives, None)
+    }
+
+    /// Replaces the snapshot with all lives, but only reports the events of the lives matching the
+    /// local filters, before or after the change.
+    fn update_filtered(
+        &mut self,
+        lives: impl IntoIterator<Item = UserLiveInfo>,
+        filter: Option<&LiveListFilter>,
+    ) -> Vec<DirectoryEvent> {
+        let matches = |live: &UserLiveInfo| match filter {
+            Some(f) => f.matches(live),
+            None => true,
+        };
+        let mut events = Vec::new();
+        let mut old_lives = std::mem::take(&mut self.lives);
+        let mut lives_by_uid = HashMap::new();
+        for live in lives.into_iter().filter(|l| l.live_data.is_some()) {
+            let _ = lives_by_uid.entry(live.author_id).or_insert(live);
+        }
+        let mut liver_uids: Vec<_> = lives_by_uid.keys().copied().collect();
+        liver_uids.sort_unstable();
+        for liver_uid in liver_uids {
+            let live = &lives_by_uid[&liver_uid];
+            let old = match old_lives.remove(&liver_uid) {
+                Some(old) => old,
+                None => {
+                    if matches(live) {
+                        events.push(DirectoryEvent::LiveStarted(Box::new(live.clone())));
+                    }
+                    continue;
+                }
+            };
+            if old.live_data.as_ref().map(|d| &d.live_id)
+                == live.live_data.as_ref().map(|d| &d.live_id)
+            {
+                if matches(&old) || matches(live) {
+                    self.diff(&old, live, &mut events);
+                }
+            } else {
+                if matches(&old) {
+                    events.push(DirectoryEvent::LiveEnded(Box::new(old)));
+                }
+                if matches(live) {
+                    events.push(DirectoryEvent::LiveStarted(Box::new(live.clone())));
+                }
+            }
+        }
+        let mut ended: Vec<_> = old_lives.into_values().filter(|l| matches(l)).collect();
+        ended.sort_unstable_by_key(|l| l.author_id);
+        events.extend(
+            ended
+                .into_iter()
+                .map(|l| DirectoryEvent::LiveEnded(Box::new(l))),
+        );
+        self.lives = lives_by_uid;
+
+        events
+    }
+
+    /// Gets all pages of the live list and updates the snapshot with it.
+    ///
+    /// The snapshot holds all lives passing the display filters, the local filters of `filter` only
+    /// select the reported events, so a live whose online count drops below `min_online_count` isn't
+    /// reported as ended. The snapshot isn't changed if any page failed.
+    ///
+    /// The pages aren't a consistent snapshot: lives shifting between pages while they are fetched
+    /// may show up twice, which are deduplicated by the liver's uid, or be missed, which are reported
+    /// as ended and started again in the next poll.
+    pub async fn poll<C>(
+        &mut self,
+        client: &ApiClient<C>,
+        filter: LiveListFilter,
+    ) -> Result<Vec<DirectoryEvent>>
+    where
+        C: pretend::client::Client + Send + Sync,
+    {
+        let lives: Vec<_> = client
+            .live_list_stream(filter.display_only())
+            .try_collect()
+            .await?;
+
+        Ok(self.update_filtered(lives, Some(&filter)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::ApiClientBuilder, mock::*};
+
+    fn lives(json: &str) -> Result<Vec<UserLiveInfo>> {
+        Ok(serde_json::from_str::<LiveList>(json)?
+            .channel_list_data
+            .live_list)
+    }
+
+    #[tokio::test]
+    async fn test_directory_tracker() -> Result<()> {
+        let mut tracker = DirectoryTracker::new().threshold(50).threshold(200);
+        let client = ApiClientBuilder::new(MockClient::new())?.build().await?;
+        let events = tracker.poll(&client, LiveListFilter::new()).await?;
+        assert_eq!(events.len(), 3);
+        assert!(events
+            .iter()
+            .all(|e| matches!(e, DirectoryEvent::LiveStarted(_))));
+        assert!(tracker.update(lives(LIVE_LIST)?).is_empty());
+
+        let mut next = lives(LIVE_LIST)?;
+        // liver 1 ended, liver 2 changed its title and type, liver 3 started another live
+        let _ = next.remove(0);
+        let data = next[0].live_data.as_mut().expect("no live data");
+        data.title = Some("new title".to_string());
+        data.live_type = Some(LiveType {
+            id: 4,
+            ..Default::default()
+        });
+        data.online_count = 60;
+        next[1].live_data.as_mut().expect("no live data").live_id = "new_live_id".to_string();
+        let events = tracker.update(next.clone());
+        assert_eq!(events.len(), 6, "{:?}", events);
+        assert!(matches!(
+            &events[0],
+            DirectoryEvent::TitleChanged { liver_uid: 2, new: Some(title), .. } if title == "new title"
+        ));
+        assert!(matches!(
+            &events[1],
+            DirectoryEvent::CategoryChanged { liver_uid: 2, old: Some(old), new: Some(new), .. }
+                if old.id == 2 && new.id == 4
+        ));
+        assert!(matches!(
+            &events[2],
+            DirectoryEvent::OnlineCountCrossed {
+                liver_uid: 2,
+                threshold: 50,
+                online_count: 60,
+                rising: true,
+                ..
+            }
+        ));
+        assert!(matches!(&events[3], DirectoryEvent::LiveEnded(l) if l.author_id == 3));
+        assert!(matches!(&events[4], DirectoryEvent::LiveStarted(l) if l.author_id == 3));
+        assert!(matches!(&events[5], DirectoryEvent::LiveEnded(l) if l.author_id == 1));
+        assert_eq!(tracker.lives().count(), 2);
+
+        next[0]
+            .live_data
+            .as_mut()
+            .expect("no live data")
+            .online_count = 10;
+        let events = tracker.update(next.clone());
+        assert!(matches!(
+            events.as_slice(),
+            [DirectoryEvent::OnlineCountCrossed {
+                threshold: 50,
+                rising: false,
+                ..
+            }]
+        ));
+
+        // lives below the local filters stay in the snapshot without being reported as ended
+        let filter = LiveListFilter::new().min_online_count(50);
+        let events = tracker.update_filtered(next.clone(), Some(&filter));
+        assert!(events.is_empty(), "{:?}", events);
+        assert_eq!(tracker.lives().count(), 2);
+        next[0]
+            .live_data
+            .as_mut()
+            .expect("no live data")
+            .online_count = 70;
+        let events = tracker.update_filtered(next.clone(), Some(&filter));
+        assert!(matches!(
+            events.as_slice(),
+            [DirectoryEvent::OnlineCountCrossed {
+                liver_uid: 2,
+                threshold: 50,
+                rising: true,
+                ..
+            }]
+        ));
+
+        // duplicated lives from shifted pages keep the first one
+        let mut duplicated = next.clone();
+        let mut shifted = next[0].clone();
+        shifted
+            .live_data
+            .as_mut()
+            .expect("no live data")
+            .online_count = 0;
+        duplicated.push(shifted);
+        assert!(tracker.update(duplicated).is_empty());
+        assert_eq!(
+            tracker
+                .live(2)
+                .and_then(|l| l.live_data.as_ref())
+                .map(|d| d.online_count),
+            Some(70)
+        );
+
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use crate::response::Captcha;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -24,10 +25,46 @@ pub enum Error {
     HeaderToStrError(#[from] pretend::http::header::ToStrError),
     #[error(transparent)]
     SerdeJsonError(#[from] serde_json::Error),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
     #[error("user login was needed")]
     NotUser,
     #[error("the liver's uid was not set")]
     NotSetLiverUid,
+    #[error("the content of the comment was empty")]
+    EmptyComment,
+    #[error("the count of likes was 0")]
+    ZeroLikeCount,
+    #[error("the stream name was empty")]
+    EmptyStreamName,
+    #[error("the live title was empty")]
+    EmptyLiveTitle,
+    #[error("gift {0} can't be sent in a batch of {1}")]
+    InvalidGiftBatchSize(i64, i32),
+    #[error("wrong account or password")]
+    WrongPassword,
+    #[error("a captcha was required to sign in")]
+    CaptchaRequired(Captcha),
+    #[error("the captcha was wrong")]
+    WrongCaptcha(Captcha),
+    #[error("the account was locked: {0}")]
+    AccountLocked(String),
+    #[error("failed to sign in, result {code}: {message}")]
+    LoginFailed { code: i32, message: String },
+    #[error("failed to sign in with the QR code, result {code}, status {status}: {message}")]
+    QrLoginFailed {
+        code: i32,
+        status: String,
+        message: String,
+    },
+    #[error("the API returned result {code}: {message}")]
+    Api { code: i32, message: String },
+    /// The token endpoint rejected the cookies of the user.
+    #[error("the token was expired: {0}")]
+    TokenExpired(String),
+    /// Getting the live info failed and the liver wasn't living.
+    #[error("the live hasn't started: {0}")]
+    LiveNotStarted(String),
 
     #[cfg(feature = "default_http_client")]
     #[error(transparent)]
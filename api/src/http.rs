@@ -15,7 +15,6 @@ fn default_reqwest_client() -> Result<reqwest::Client> {
         .pool_idle_timeout(IDLE_TIMEOUT)
         .tcp_keepalive(KEEPALIVE)
         .use_rustls_tls()
-        .no_trust_dns()
         .https_only(true)
         .build()?)
 }
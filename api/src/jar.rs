@@ -0,0 +1,304 @@
+//! A cookie jar shared by all HTTP clients of `ApiClient`.
+
+use crate::Result;
+use cookie::Cookie;
+use pretend::{
+    client::{async_trait, Bytes, Client, Method},
+    http::header::{HeaderValue, COOKIE, SET_COOKIE},
+    HeaderMap, Response, Url,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct StoredCookie {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    expires: Option<SystemTime>,
+}
+
+impl StoredCookie {
+    #[inline]
+    fn is_expired(&self, now: SystemTime) -> bool {
+        matches!(self.expires, Some(expires) if expires <= now)
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host,
+            None => return false,
+        };
+        let domain_matched = if self.host_only {
+            host == self.domain
+        } else {
+            host == self.domain
+                || (host.ends_with(&self.domain)
+                    && host[..host.len() - self.domain.len()].ends_with('.'))
+        };
+        let path = url.path();
+        let path_matched = path == self.path
+            || (path.starts_with(&self.path)
+                && (self.path.ends_with('/') || path[self.path.len()..].starts_with('/')));
+
+        domain_matched && path_matched && (!self.secure || url.scheme() == "https")
+    }
+}
+
+/// The default path of a cookie without the `Path` attribute, see RFC 6265 section 5.1.4.
+#[inline]
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => url.path()[..i].to_string(),
+    }
+}
+
+/// Stores the cookies from `Set-Cookie` and returns the cookies matching the URL.
+///
+/// Clones share the same cookies.
+#[derive(Clone, Debug, Default)]
+pub struct CookieJar(Arc<Mutex<Vec<StoredCookie>>>);
+
+impl CookieJar {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn insert(&self, cookie: StoredCookie) {
+        let mut cookies = self.0.lock().expect("the cookie jar was poisoned");
+        cookies.retain(|c| {
+            !(c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path)
+        });
+        if !cookie.is_expired(SystemTime::now()) {
+            cookies.push(cookie);
+        }
+    }
+
+    /// Stores the cookie in a `Set-Cookie` header received from the URL.
+    pub fn store(&self, url: &Url, set_cookie: &str) -> Result<()> {
+        let cookie = Cookie::parse(set_cookie)?;
+        let host = url.host_str().unwrap_or_default();
+        let (domain, host_only) = match cookie.domain().map(|d| d.trim_start_matches('.')) {
+            Some(domain) if !domain.is_empty() => {
+                let domain = domain.to_ascii_lowercase();
+                // a server can't set cookies for other domains
+                if !(host == domain || host.ends_with(&format!(".{}", domain))) {
+                    return Ok(());
+                }
+                (domain, false)
+            }
+            _ => (host.to_string(), true),
+        };
+        let expires = match (cookie.max_age(), cookie.expires_datetime()) {
+            (Some(max_age), _) => Some(
+                SystemTime::now()
+                    .checked_add(Duration::from_secs(max_age.whole_seconds().max(0) as u64))
+                    .unwrap_or(UNIX_EPOCH),
+            ),
+            (None, Some(expires)) => Some(
+                UNIX_EPOCH
+                    .checked_add(Duration::from_secs(expires.unix_timestamp().max(0) as u64))
+                    .unwrap_or(UNIX_EPOCH),
+            ),
+            (None, None) => None,
+        };
+        self.insert(StoredCookie {
+            name: cookie.name().to_string(),
+            value: cookie.value().to_string(),
+            domain,
+            host_only,
+            path: match cookie.path() {
+                Some(path) if path.starts_with('/') => path.to_string(),
+                _ => default_path(url),
+            },
+            secure: cookie.secure().unwrap_or_default(),
+            expires,
+        });
+
+        Ok(())
+    }
+
+    /// Stores the cookies in all `Set-Cookie` headers of the response from the URL, invalid cookies are ignored.
+    pub fn store_response(&self, url: &Url, headers: &HeaderMap) {
+        for value in headers.get_all(SET_COOKIE) {
+            if let Ok(set_cookie) = value.to_str() {
+                let _ = self.store(url, set_cookie);
+            }
+        }
+    }
+
+    /// Adds cookies in the form of `name1=value1; name2=value2` for the domain and its subdomains.
+    pub fn add_cookies(&self, domain: &str, cookies: &str) {
+        for (name, value) in cookies
+            .split(';')
+            .filter_map(|c| c.trim().split_once('='))
+            .filter(|(name, _)| !name.is_empty())
+        {
+            self.insert(StoredCookie {
+                name: name.to_string(),
+                value: value.to_string(),
+                domain: domain.trim_start_matches('.').to_ascii_lowercase(),
+                host_only: false,
+                path: "/".to_string(),
+                secure: false,
+                expires: None,
+            });
+        }
+    }
+
+    /// Returns the unexpired cookies matching the URL in the form of a `Cookie` header.
+    pub fn cookies(&self, url: &Url) -> Option<String> {
+        let now = SystemTime::now();
+        let mut cookies = self.0.lock().expect("the cookie jar was poisoned");
+        cookies.retain(|c| !c.is_expired(now));
+        let mut matched: Vec<_> = cookies.iter().filter(|c| c.matches(url)).collect();
+        if matched.is_empty() {
+            return None;
+        }
+        // cookies with longer paths are listed first
+        matched.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+
+        Some(
+            matched
+                .into_iter()
+                .map(|c| format!("{}={}", c.name, c.value))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+
+    /// Returns the value of the unexpired cookie matching the URL.
+    pub fn get(&self, url: &Url, name: &str) -> Option<String> {
+        let now = SystemTime::now();
+        self.0
+            .lock()
+            .expect("the cookie jar was poisoned")
+            .iter()
+            .filter(|c| c.name == name && !c.is_expired(now) && c.matches(url))
+            .max_by_key(|c| c.path.len())
+            .map(|c| c.value.clone())
+    }
+
+    /// Copies the cookies to a new jar which doesn't share cookies with this one.
+    #[inline]
+    pub fn deep_clone(&self) -> Self {
+        Self(Arc::new(Mutex::new(
+            self.0.lock().expect("the cookie jar was poisoned").clone(),
+        )))
+    }
+
+    #[inline]
+    pub fn clear(&self) {
+        self.0.lock().expect("the cookie jar was poisoned").clear();
+    }
+}
+
+/// A `pretend` client which attaches the cookies in the jar to requests and stores the cookies in responses.
+#[derive(Clone, Debug)]
+pub struct CookieClient<C> {
+    client: C,
+    jar: CookieJar,
+}
+
+impl<C> CookieClient<C> {
+    #[inline]
+    pub fn new(client: C, jar: CookieJar) -> Self {
+        Self { client, jar }
+    }
+
+    #[inline]
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+
+    #[inline]
+    pub fn jar(&self) -> &CookieJar {
+        &self.jar
+    }
+}
+
+#[async_trait]
+impl<C> Client for CookieClient<C>
+where
+    C: Client + Send + Sync,
+{
+    async fn execute(
+        &self,
+        method: Method,
+        url: Url,
+        mut headers: HeaderMap,
+        body: Option<Bytes>,
+    ) -> pretend::Result<Response<Bytes>> {
+        if !headers.contains_key(COOKIE) {
+            if let Some(cookies) = self.jar.cookies(&url) {
+                let _ = headers.insert(
+                    COOKIE,
+                    HeaderValue::from_str(&cookies).map_err(pretend::Error::request)?,
+                );
+            }
+        }
+        let response = self
+            .client
+            .execute(method, url.clone(), headers, body)
+            .await?;
+        self.jar.store_response(&url, response.headers());
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cookie_jar() -> Result<()> {
+        let jar = CookieJar::new();
+        let login = Url::parse("https://id.app.acfun.cn/rest/web/login/signin")?;
+        let live = Url::parse("https://live.acfun.cn/api/live/info")?;
+        let other = Url::parse("https://api.kuaishouzt.com/")?;
+        jar.store(&login, "auth_key=10000; Domain=.acfun.cn; Path=/")?;
+        jar.store(&login, "host_only=1; Path=/")?;
+        jar.store(&login, "login_path=1")?;
+        jar.store(&login, "expired=1; Domain=acfun.cn; Max-Age=0")?;
+        jar.store(
+            &login,
+            "old=1; Domain=acfun.cn; Expires=Thu, 01 Jan 1970 00:00:01 GMT",
+        )?;
+        jar.store(&login, "secure=1; Domain=acfun.cn; Path=/; Secure")?;
+        jar.store(&login, "other=1; Domain=kuaishouzt.com")?;
+        assert_eq!(
+            jar.cookies(&login).as_deref(),
+            Some("login_path=1; auth_key=10000; host_only=1; secure=1")
+        );
+        assert_eq!(
+            jar.cookies(&live).as_deref(),
+            Some("auth_key=10000; secure=1")
+        );
+        assert_eq!(
+            jar.cookies(&Url::parse("http://live.acfun.cn/")?)
+                .as_deref(),
+            Some("auth_key=10000")
+        );
+        assert_eq!(jar.cookies(&other), None);
+
+        jar.store(&live, "auth_key=20000; Domain=acfun.cn; Path=/")?;
+        assert_eq!(jar.get(&login, "auth_key").as_deref(), Some("20000"));
+        jar.store(&live, "auth_key=; Domain=acfun.cn; Path=/; Max-Age=0")?;
+        assert_eq!(jar.get(&login, "auth_key"), None);
+
+        jar.clear();
+        jar.add_cookies("acfun.cn", "a=1; b=2");
+        assert_eq!(jar.cookies(&live).as_deref(), Some("a=1; b=2"));
+        assert_eq!(jar.cookies(&other), None);
+
+        Ok(())
+    }
+}
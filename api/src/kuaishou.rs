@@ -1,4 +1,4 @@
-use crate::response::*;
+use crate::{checked::KsChecked, response::*};
 use pretend::{pretend, Json, Result};
 use serde::Serialize;
 
@@ -46,6 +46,135 @@ impl<'a> KsForm<'a> {
     }
 }
 
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CommentForm<'a> {
+    visitor_id: i64,
+    live_id: &'a str,
+    content: &'a str,
+}
+
+impl<'a> CommentForm<'a> {
+    #[inline]
+    pub(crate) fn new(user_id: i64, live_id: &'a str, content: &'a str) -> Self {
+        Self {
+            visitor_id: user_id,
+            live_id,
+            content,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LikeForm<'a> {
+    visitor_id: i64,
+    live_id: &'a str,
+    count: u32,
+}
+
+impl<'a> LikeForm<'a> {
+    #[inline]
+    pub(crate) fn new(user_id: i64, live_id: &'a str, count: u32) -> Self {
+        Self {
+            visitor_id: user_id,
+            live_id,
+            count,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GiftForm<'a> {
+    visitor_id: i64,
+    live_id: &'a str,
+    gift_id: i64,
+    batch_size: i32,
+    combo_key: &'a str,
+}
+
+impl<'a> GiftForm<'a> {
+    #[inline]
+    pub(crate) fn new(
+        user_id: i64,
+        live_id: &'a str,
+        gift_id: i64,
+        batch_size: i32,
+        combo_key: &'a str,
+    ) -> Self {
+        Self {
+            visitor_id: user_id,
+            live_id,
+            gift_id,
+            batch_size,
+            combo_key,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ManagerForm {
+    visitor_id: i64,
+    target_user_id: i64,
+}
+
+impl ManagerForm {
+    #[inline]
+    pub(crate) fn new(user_id: i64, target_user_id: i64) -> Self {
+        Self {
+            visitor_id: user_id,
+            target_user_id,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct KickForm<'a> {
+    visitor_id: i64,
+    live_id: &'a str,
+    kicked_user_id: i64,
+}
+
+impl<'a> KickForm<'a> {
+    #[inline]
+    pub(crate) fn new(user_id: i64, live_id: &'a str, kicked_user_id: i64) -> Self {
+        Self {
+            visitor_id: user_id,
+            live_id,
+            kicked_user_id,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MuteForm<'a> {
+    visitor_id: i64,
+    live_id: &'a str,
+    target_user_id: i64,
+    duration_ms: i64,
+}
+
+impl<'a> MuteForm<'a> {
+    #[inline]
+    pub(crate) fn new(
+        user_id: i64,
+        live_id: &'a str,
+        target_user_id: i64,
+        duration_ms: i64,
+    ) -> Self {
+        Self {
+            visitor_id: user_id,
+            live_id,
+            target_user_id,
+            duration_ms,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct StartPlayForm<'a> {
@@ -67,13 +196,116 @@ impl<'a> StartPlayForm<'a> {
 pub(crate) trait KuaishouZt {
     #[request(method = "POST", path = "/rest/zt/live/web/startPlay")]
     #[header(name = "Referer", value = "https://live.acfun.cn/")]
-    async fn start_play(&self, query: &KsQuery, form: &StartPlayForm) -> Result<Json<LiveInfo>>;
+    #[header(name = "X-AcFunLive-Idempotent", value = "1")]
+    async fn start_play(
+        &self,
+        query: &KsQuery,
+        form: &StartPlayForm,
+    ) -> Result<Json<KsChecked<LiveInfo>>>;
 
     #[request(method = "POST", path = "/rest/zt/live/web/gift/list")]
     #[header(name = "Referer", value = "https://live.acfun.cn/")]
-    async fn gift_list(&self, query: &KsQuery, form: &KsForm) -> Result<Json<GiftList>>;
+    #[header(name = "X-AcFunLive-Idempotent", value = "1")]
+    async fn gift_list(&self, query: &KsQuery, form: &KsForm) -> Result<Json<KsChecked<GiftList>>>;
 
     #[request(method = "POST", path = "/rest/zt/live/web/endSummary")]
     #[header(name = "Referer", value = "https://live.acfun.cn/")]
-    async fn end_summary(&self, query: &KsQuery, form: &KsForm) -> Result<Json<Summary>>;
+    #[header(name = "X-AcFunLive-Idempotent", value = "1")]
+    async fn end_summary(&self, query: &KsQuery, form: &KsForm)
+        -> Result<Json<KsChecked<Summary>>>;
+
+    #[request(method = "POST", path = "/rest/zt/live/web/audience/action/comment")]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn comment(
+        &self,
+        query: &KsQuery,
+        form: &CommentForm,
+    ) -> Result<Json<KsChecked<ActionResult>>>;
+
+    #[request(method = "POST", path = "/rest/zt/live/web/audience/action/like")]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn like(&self, query: &KsQuery, form: &LikeForm)
+        -> Result<Json<KsChecked<ActionResult>>>;
+
+    #[request(method = "POST", path = "/rest/zt/live/web/gift/send")]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn send_gift(
+        &self,
+        query: &KsQuery,
+        form: &GiftForm,
+    ) -> Result<Json<KsChecked<SendGift>>>;
+}
+
+#[pretend]
+pub(crate) trait KuaishouZtManager {
+    #[request(method = "POST", path = "/rest/zt/live/web/author/action/manager/list")]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    #[header(name = "X-AcFunLive-Idempotent", value = "1")]
+    async fn manager_list(
+        &self,
+        query: &KsQuery,
+        form: &KsForm,
+    ) -> Result<Json<KsChecked<ManagerList>>>;
+
+    #[request(method = "POST", path = "/rest/zt/live/web/author/action/manager/add")]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn add_manager(
+        &self,
+        query: &KsQuery,
+        form: &ManagerForm,
+    ) -> Result<Json<KsChecked<ActionResult>>>;
+
+    #[request(
+        method = "POST",
+        path = "/rest/zt/live/web/author/action/manager/delete"
+    )]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn delete_manager(
+        &self,
+        query: &KsQuery,
+        form: &ManagerForm,
+    ) -> Result<Json<KsChecked<ActionResult>>>;
+
+    #[request(method = "POST", path = "/rest/zt/live/web/author/action/kick")]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn author_kick(
+        &self,
+        query: &KsQuery,
+        form: &KickForm,
+    ) -> Result<Json<KsChecked<ActionResult>>>;
+
+    #[request(method = "POST", path = "/rest/zt/live/web/manager/kick")]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn manager_kick(
+        &self,
+        query: &KsQuery,
+        form: &KickForm,
+    ) -> Result<Json<KsChecked<ActionResult>>>;
+
+    #[request(method = "POST", path = "/rest/zt/live/web/manager/mute")]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn mute(&self, query: &KsQuery, form: &MuteForm)
+        -> Result<Json<KsChecked<ActionResult>>>;
+
+    #[request(
+        method = "POST",
+        path = "/rest/zt/live/web/author/action/blacklist/add"
+    )]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn block(
+        &self,
+        query: &KsQuery,
+        form: &ManagerForm,
+    ) -> Result<Json<KsChecked<ActionResult>>>;
+
+    #[request(
+        method = "POST",
+        path = "/rest/zt/live/web/author/action/blacklist/delete"
+    )]
+    #[header(name = "Referer", value = "https://live.acfun.cn/")]
+    async fn unblock(
+        &self,
+        query: &KsQuery,
+        form: &ManagerForm,
+    ) -> Result<Json<KsChecked<ActionResult>>>;
 }
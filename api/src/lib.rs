@@ -1,12 +1,23 @@
 #![forbid(unsafe_code)]
 
+pub mod author;
 pub mod client;
+pub mod directory;
+pub mod jar;
+pub mod live_list;
 pub mod response;
+pub mod retry;
+pub mod store;
+pub mod stream;
 
 #[cfg(feature = "default_http_client")]
 pub mod http;
 
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+
 mod acfun;
+mod checked;
 mod errors;
 mod kuaishou;
 mod rest;
@@ -0,0 +1,205 @@
+//! Browses the live list page by page.
+
+use crate::{client::ApiClient, response::*, Result};
+use futures::{
+    future,
+    stream::{self, BoxStream, StreamExt, TryStreamExt},
+};
+
+const NO_MORE: &str = "no_more";
+const DEFAULT_PAGE_SIZE: u32 = 100;
+
+/// Filters the lives of `ApiClient::live_list_stream()`.
+///
+/// Display filters are applied by AcFun, the others are applied to every page locally.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct LiveListFilter {
+    page_size: u32,
+    display_filters: Vec<DisplayFilter>,
+    categories: Vec<i32>,
+    live_types: Vec<i32>,
+    min_online_count: i32,
+}
+
+impl Default for LiveListFilter {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            page_size: DEFAULT_PAGE_SIZE,
+            display_filters: Vec::new(),
+            categories: Vec::new(),
+            live_types: Vec::new(),
+            min_online_count: 0,
+        }
+    }
+}
+
+impl LiveListFilter {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the count of lives requested in a page, the default is 100.
+    #[inline]
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    /// Adds a display filter from `LiveList::channel_filters`, lives matching any of them are returned.
+    #[inline]
+    pub fn display_filter(mut self, filter: DisplayFilter) -> Self {
+        self.display_filters.push(filter);
+        self
+    }
+
+    /// Adds a category, i.e. `LiveType::category_id`, lives in any of the categories are returned.
+    #[inline]
+    pub fn category(mut self, category_id: i32) -> Self {
+        self.categories.push(category_id);
+        self
+    }
+
+    /// Adds a live type, i.e. `LiveType::id`, lives of any of the types are returned.
+    #[inline]
+    pub fn live_type(mut self, live_type_id: i32) -> Self {
+        self.live_types.push(live_type_id);
+        self
+    }
+
+    #[inline]
+    pub fn min_online_count(mut self, count: i32) -> Self {
+        self.min_online_count = count;
+        self
+    }
+
+    /// Keeps the page size and the display filters, drops the local filters.
+    #[inline]
+    pub(crate) fn display_only(&self) -> Self {
+        Self {
+            page_size: self.page_size,
+            display_filters: self.display_filters.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Whether the live matches the local filters.
+    pub fn matches(&self, info: &UserLiveInfo) -> bool {
+        let data = match info.live_data.as_ref() {
+            Some(data) => data,
+            None => return false,
+        };
+        let live_type = data.live_type.as_ref();
+        (self.categories.is_empty()
+            || matches!(live_type, Some(t) if self.categories.contains(&t.category_id)))
+            && (self.live_types.is_empty()
+                || matches!(live_type, Some(t) if self.live_types.contains(&t.id)))
+            && data.online_count >= self.min_online_count
+    }
+}
+
+impl<C> ApiClient<C>
+where
+    C: pretend::client::Client + Send + Sync,
+{
+    /// Gets the lives in the page and the `pcursor` of the next page, `None` if it was the last page.
+    async fn live_list_page(
+        &self,
+        pcursor: Option<String>,
+        filter: &LiveListFilter,
+    ) -> Result<Option<(Vec<UserLiveInfo>, Option<String>)>> {
+        let pcursor = match pcursor {
+            Some(pcursor) => pcursor,
+            None => return Ok(None),
+        };
+        let page = self
+            .get_live_list_page(filter.page_size, &pcursor, &filter.display_filters)
+            .await?
+            .channel_list_data;
+        let has_lives = !page.live_list.is_empty();
+        let next = Some(page.pcursor).filter(|p| has_lives && !p.is_empty() && p != NO_MORE);
+
+        Ok(Some((page.live_list, next)))
+    }
+
+    /// Returns the lives in the live list page by page, following `pcursor` until the last page.
+    ///
+    /// The stream ends after an error.
+    pub fn live_list_stream(&self, filter: LiveListFilter) -> BoxStream<'_, Result<UserLiveInfo>> {
+        let page_filter = filter.clone();
+        stream::try_unfold(Some(String::new()), move |pcursor| {
+            let filter = page_filter.clone();
+            async move {
+                self.live_list_page(pcursor, &filter).await.map(|page| {
+                    page.map(|(lives, next)| (stream::iter(lives.into_iter().map(Ok)), next))
+                })
+            }
+        })
+        .try_flatten()
+        .try_filter(move |info| future::ready(filter.matches(info)))
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::ApiClientBuilder, mock::*};
+    use pretend::client::Method;
+
+    #[tokio::test]
+    async fn test_live_list_stream() -> Result<()> {
+        let mock = MockClient::new();
+        let client = ApiClientBuilder::new(mock.clone())?.build().await?;
+        let _ = mock.json(
+            Method::GET,
+            "/api/channel/list?count=100&pcursor=",
+            LIVE_LIST_PAGE,
+        );
+        let lives: Vec<_> = client
+            .live_list_stream(LiveListFilter::new())
+            .map(|info| info.map(|i| i.author_id))
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<_>>()?;
+        assert_eq!(lives, vec![4, 5, 1, 2, 3]);
+        let pages: Vec<_> = mock
+            .requests()
+            .into_iter()
+            .filter(|r| r.url.path() == "/api/channel/list")
+            .map(|r| r.url.query().unwrap_or_default().to_string())
+            .collect();
+        assert_eq!(
+            pages,
+            vec!["count=100&pcursor=", "count=100&pcursor=mock_pcursor"]
+        );
+
+        let lives: Vec<_> = client
+            .live_list_stream(LiveListFilter::new().category(2).min_online_count(10))
+            .map(|info| info.map(|i| i.author_id))
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<_>>()?;
+        assert_eq!(lives, vec![4, 3]);
+
+        mock.clear_requests();
+        let filter = DisplayFilter {
+            filter_type: 3,
+            filter_id: 1,
+            ..Default::default()
+        };
+        let _ = client
+            .live_list_stream(LiveListFilter::new().page_size(2).display_filter(filter))
+            .next()
+            .await;
+        assert_eq!(
+            mock.requests()[0].url.query(),
+            Some("count=2&pcursor=&filters=%5B%7B%22filterType%22%3A3%2C%22filterId%22%3A1%7D%5D")
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,325 @@
+//! An offline stand-in for the AcFun HTTP servers, serving the canned JSON in `fixtures/`.
+
+use pretend::{
+    client::{async_trait, Bytes, Client, Method},
+    http::header::{HeaderName, HeaderValue, CONTENT_TYPE, SET_COOKIE},
+    HeaderMap, Response, Result, StatusCode, Url,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+pub const LOGIN: &str = include_str!("../fixtures/login.json");
+pub const LOGIN_WRONG_PASSWORD: &str = include_str!("../fixtures/login_wrong_password.json");
+pub const LOGIN_CAPTCHA_REQUIRED: &str = include_str!("../fixtures/login_captcha_required.json");
+pub const LOGIN_ACCOUNT_LOCKED: &str = include_str!("../fixtures/login_account_locked.json");
+pub const CAPTCHA: &str = include_str!("../fixtures/captcha.json");
+pub const SMS_CODE: &str = include_str!("../fixtures/sms_code.json");
+pub const QR_START: &str = include_str!("../fixtures/qr_start.json");
+pub const QR_SCAN_RESULT: &str = include_str!("../fixtures/qr_scan_result.json");
+pub const QR_ACCEPT_RESULT: &str = include_str!("../fixtures/qr_accept_result.json");
+pub const VISITOR_TOKEN: &str = include_str!("../fixtures/visitor_token.json");
+pub const USER_TOKEN: &str = include_str!("../fixtures/user_token.json");
+pub const LIVE_INFO: &str = include_str!("../fixtures/live_info.json");
+pub const GIFT_LIST: &str = include_str!("../fixtures/gift_list.json");
+pub const SUMMARY: &str = include_str!("../fixtures/summary.json");
+pub const LIVE_LIST: &str = include_str!("../fixtures/live_list.json");
+/// The first page of the live list, `LIVE_LIST` is the last page.
+pub const LIVE_LIST_PAGE: &str = include_str!("../fixtures/live_list_page.json");
+pub const USER_LIVE_INFO: &str = include_str!("../fixtures/user_live_info.json");
+/// The `UserLiveInfo` of a liver who isn't living, not served by default.
+pub const USER_LIVE_OFFLINE: &str = include_str!("../fixtures/user_live_offline.json");
+pub const MEDAL_LIST: &str = include_str!("../fixtures/medal_list.json");
+pub const MEDAL_RANK_LIST: &str = include_str!("../fixtures/medal_rank_list.json");
+pub const ACTION_RESULT: &str = include_str!("../fixtures/action_result.json");
+pub const SEND_GIFT: &str = include_str!("../fixtures/send_gift.json");
+pub const MANAGER_LIST: &str = include_str!("../fixtures/manager_list.json");
+pub const PUSH_CONFIG: &str = include_str!("../fixtures/push_config.json");
+pub const AUTHOR_LIVE_STATUS: &str = include_str!("../fixtures/author_live_status.json");
+pub const START_LIVE: &str = include_str!("../fixtures/start_live.json");
+pub const STOP_LIVE: &str = include_str!("../fixtures/stop_live.json");
+pub const TOKEN_EXPIRED: &str = include_str!("../fixtures/token_expired.json");
+pub const LIVE_NOT_STARTED: &str = include_str!("../fixtures/live_not_started.json");
+
+pub const MOCK_DEVICE_ID: &str = "web_mock_device_id";
+pub const MOCK_USER_ID: i64 = 10000;
+pub const MOCK_VISITOR_ID: i64 = 1_000_000_000;
+pub const MOCK_LIVER_UID: i64 = 1;
+pub const MOCK_LIVE_ID: &str = "mock_live_id";
+
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl MockResponse {
+    #[inline]
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body: Bytes::new(),
+        }
+    }
+
+    #[inline]
+    pub fn json(body: impl Into<Bytes>) -> Self {
+        Self::new(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+    }
+
+    /// Appends a header, the value must only contain visible ASCII characters.
+    #[inline]
+    pub fn header(mut self, name: HeaderName, value: &'static str) -> Self {
+        let _ = self.headers.append(name, HeaderValue::from_static(value));
+        self
+    }
+
+    #[inline]
+    pub fn body(mut self, body: impl Into<Bytes>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MockRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: Option<Bytes>,
+}
+
+type Route = (Method, String);
+
+/// A `pretend` client which never touches the network.
+///
+/// Requests are matched by their method and URL path, or the path with the query if there is such a
+/// route, e.g. `/api/channel/list?count=100&pcursor=`. Unknown routes get `404 Not Found`.
+/// Clones share the same routes and request log.
+#[derive(Clone, Debug, Default)]
+pub struct MockClient {
+    routes: Arc<Mutex<HashMap<Route, MockResponse>>>,
+    once: Arc<Mutex<HashMap<Route, VecDeque<MockResponse>>>>,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockClient {
+    /// Creates a client serving all fixtures.
+    pub fn new() -> Self {
+        let client = Self::empty();
+        let _ = client
+            .route(
+                Method::POST,
+                "/rest/web/login/signin",
+                MockResponse::json(LOGIN)
+                    .header(SET_COOKIE, "acPasssToken=mock_pass_token; Path=/")
+                    .header(SET_COOKIE, "auth_key=10000; Path=/")
+                    .header(SET_COOKIE, "safety_id=mock_safety_id; Path=/"),
+            )
+            .route(
+                Method::POST,
+                "/rest/web/login/sms/signin",
+                MockResponse::json(LOGIN)
+                    .header(SET_COOKIE, "acPasssToken=mock_pass_token; Path=/")
+                    .header(SET_COOKIE, "auth_key=10000; Path=/")
+                    .header(SET_COOKIE, "safety_id=mock_safety_id; Path=/"),
+            )
+            .route(
+                Method::GET,
+                "/rest/pc-direct/qr/acceptResult",
+                MockResponse::json(QR_ACCEPT_RESULT)
+                    .header(SET_COOKIE, "acPasssToken=mock_pass_token; Path=/")
+                    .header(SET_COOKIE, "auth_key=10000; Path=/")
+                    .header(SET_COOKIE, "safety_id=mock_safety_id; Path=/"),
+            )
+            .route(
+                Method::GET,
+                "/",
+                MockResponse::new(StatusCode::OK)
+                    .header(SET_COOKIE, "_did=web_mock_device_id; Path=/"),
+            )
+            .json(Method::POST, "/rest/web/login/captcha", CAPTCHA)
+            .json(Method::POST, "/rest/web/login/sms/send", SMS_CODE)
+            .json(Method::GET, "/rest/pc-direct/qr/start", QR_START)
+            .json(Method::GET, "/rest/pc-direct/qr/scanResult", QR_SCAN_RESULT)
+            .json(Method::POST, "/rest/app/visitor/login", VISITOR_TOKEN)
+            .json(Method::POST, "/rest/web/token/get", USER_TOKEN)
+            .json(Method::POST, "/rest/zt/live/web/startPlay", LIVE_INFO)
+            .json(Method::POST, "/rest/zt/live/web/gift/list", GIFT_LIST)
+            .json(Method::POST, "/rest/zt/live/web/endSummary", SUMMARY)
+            .json(
+                Method::POST,
+                "/rest/zt/live/web/audience/action/comment",
+                ACTION_RESULT,
+            )
+            .json(
+                Method::POST,
+                "/rest/zt/live/web/audience/action/like",
+                ACTION_RESULT,
+            )
+            .json(Method::POST, "/rest/zt/live/web/gift/send", SEND_GIFT)
+            .json(
+                Method::POST,
+                "/rest/zt/live/web/author/action/manager/list",
+                MANAGER_LIST,
+            )
+            .json(
+                Method::POST,
+                "/rest/zt/live/web/author/action/manager/add",
+                ACTION_RESULT,
+            )
+            .json(
+                Method::POST,
+                "/rest/zt/live/web/author/action/manager/delete",
+                ACTION_RESULT,
+            )
+            .json(
+                Method::POST,
+                "/rest/zt/live/web/author/action/kick",
+                ACTION_RESULT,
+            )
+            .json(
+                Method::POST,
+                "/rest/zt/live/web/manager/kick",
+                ACTION_RESULT,
+            )
+            .json(
+                Method::POST,
+                "/rest/zt/live/web/manager/mute",
+                ACTION_RESULT,
+            )
+            .json(
+                Method::POST,
+                "/rest/zt/live/web/author/action/blacklist/add",
+                ACTION_RESULT,
+            )
+            .json(
+                Method::POST,
+                "/rest/zt/live/web/author/action/blacklist/delete",
+                ACTION_RESULT,
+            )
+            .json(Method::POST, "/rest/zt/live/web/obs/config", PUSH_CONFIG)
+            .json(
+                Method::POST,
+                "/rest/zt/live/web/obs/status",
+                AUTHOR_LIVE_STATUS,
+            )
+            .json(Method::POST, "/rest/zt/live/web/obs/startPush", START_LIVE)
+            .json(Method::POST, "/rest/zt/live/web/obs/stopPush", STOP_LIVE)
+            .json(
+                Method::POST,
+                "/rest/zt/live/web/obs/changeCaption",
+                ACTION_RESULT,
+            )
+            .json(Method::GET, "/api/channel/list", LIVE_LIST)
+            .json(
+                Method::GET,
+                "/rest/pc-direct/fansClub/fans/medal/list",
+                MEDAL_LIST,
+            )
+            .json(Method::GET, "/api/live/info", USER_LIVE_INFO)
+            .json(
+                Method::GET,
+                "/rest/pc-direct/fansClub/friendshipDegreeRankInfo",
+                MEDAL_RANK_LIST,
+            );
+
+        client
+    }
+
+    /// Creates a client without any route.
+    #[inline]
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces the response of the route.
+    #[inline]
+    pub fn route(&self, method: Method, path: &str, response: MockResponse) -> &Self {
+        let _ = self
+            .routes
+            .lock()
+            .expect("the mock routes were poisoned")
+            .insert((method, path.to_string()), response);
+        self
+    }
+
+    /// Queues a response which is served only once before the responses of the route.
+    #[inline]
+    pub fn route_once(&self, method: Method, path: &str, response: MockResponse) -> &Self {
+        self.once
+            .lock()
+            .expect("the mock routes were poisoned")
+            .entry((method, path.to_string()))
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    #[inline]
+    pub fn json(&self, method: Method, path: &str, body: &'static str) -> &Self {
+        self.route(method, path, MockResponse::json(body))
+    }
+
+    /// Returns all requests received so far.
+    #[inline]
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests
+            .lock()
+            .expect("the mock request log was poisoned")
+            .clone()
+    }
+
+    #[inline]
+    pub fn clear_requests(&self) {
+        self.requests
+            .lock()
+            .expect("the mock request log was poisoned")
+            .clear();
+    }
+}
+
+#[async_trait]
+impl Client for MockClient {
+    async fn execute(
+        &self,
+        method: Method,
+        url: Url,
+        headers: HeaderMap,
+        body: Option<Bytes>,
+    ) -> Result<Response<Bytes>> {
+        let once = {
+            let mut once = self.once.lock().expect("the mock routes were poisoned");
+            once.get_mut(&(method.clone(), url.path().to_string()))
+                .and_then(VecDeque::pop_front)
+        };
+        let response = once.unwrap_or_else(|| {
+            let routes = self.routes.lock().expect("the mock routes were poisoned");
+            url.query()
+                .and_then(|q| routes.get(&(method.clone(), format!("{}?{}", url.path(), q))))
+                .or_else(|| routes.get(&(method.clone(), url.path().to_string())))
+                .cloned()
+                .unwrap_or_else(|| MockResponse::new(StatusCode::NOT_FOUND))
+        });
+        self.requests
+            .lock()
+            .expect("the mock request log was poisoned")
+            .push(MockRequest {
+                method,
+                url,
+                headers,
+                body,
+            });
+
+        Ok(Response::new(
+            response.status,
+            response.headers,
+            response.body,
+        ))
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::time::Instant;
 
 fn deserialize_stream_info<'de, D>(deserializer: D) -> Result<StreamInfo, D::Error>
 where
@@ -66,9 +67,102 @@ where
 #[serde(rename_all = "camelCase")]
 pub struct Login {
     pub result: i32,
+    #[serde(default)]
     pub img: String,
+    #[serde(default)]
     pub user_id: i64,
+    #[serde(default)]
     pub username: String,
+    #[serde(default)]
+    pub key: String,
+    #[serde(default, rename = "error_msg")]
+    pub error_msg: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct SmsCode {
+    pub result: i32,
+    #[serde(default, rename = "error_msg")]
+    pub error_msg: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QrLogin {
+    pub result: i32,
+    pub qr_login_token: String,
+    pub qr_login_signature: String,
+    /// The QR code image encoded in base64.
+    pub image_data: String,
+    /// Milliseconds before the QR code expires.
+    pub expire_time: i64,
+    /// When the QR code expires, set by `ApiClient::start_qr_login()`.
+    #[serde(skip)]
+    pub expires_at: Option<Instant>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QrScanResult {
+    pub result: i32,
+    #[serde(default)]
+    pub qr_login_signature: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default, rename = "error_msg")]
+    pub error_msg: String,
+}
+
+impl QrScanResult {
+    #[inline]
+    pub fn is_scanned(&self) -> bool {
+        self.result == 0 && self.status == "SCANNED"
+    }
+
+    /// Returns `true` if the QR code expired or was cancelled, otherwise it should be polled again.
+    #[inline]
+    pub fn is_failed(&self) -> bool {
+        is_qr_failed(&self.status)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QrAcceptResult {
+    pub result: i32,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub user_id: i64,
+    #[serde(default, rename = "error_msg")]
+    pub error_msg: String,
+}
+
+impl QrAcceptResult {
+    #[inline]
+    pub fn is_accepted(&self) -> bool {
+        self.result == 0 && self.status == "ACCEPTED"
+    }
+
+    /// Returns `true` if the QR code expired or was cancelled, otherwise it should be polled again.
+    #[inline]
+    pub fn is_failed(&self) -> bool {
+        is_qr_failed(&self.status)
+    }
+}
+
+#[inline]
+fn is_qr_failed(status: &str) -> bool {
+    matches!(status, "EXPIRED" | "CANCELLED" | "CANCEL")
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Captcha {
+    pub result: i32,
+    /// The key which should be sent back with the solved captcha.
+    pub key: String,
+    /// The captcha image encoded in base64.
+    pub img: String,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
@@ -281,6 +375,119 @@ pub struct ExternalDisplayGift {
     pub tips_delay_time: i64,
 }
 
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ActionResult {
+    pub result: i32,
+    pub host: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct SendGift {
+    pub result: i32,
+    pub data: SendGiftData,
+    pub host: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendGiftData {
+    pub combo_key: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ManagerList {
+    pub result: i32,
+    pub data: ManagerData,
+    pub host: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManagerData {
+    pub list: Vec<Manager>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manager {
+    pub user_info: ManagerInfo,
+    pub custom_data: String,
+    pub online: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManagerInfo {
+    pub user_id: i64,
+    pub nickname: String,
+    pub avatar: Vec<GiftPicture>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct PushConfig {
+    pub result: i32,
+    pub data: PushConfigData,
+    pub host: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushConfigData {
+    pub stream_name: String,
+    pub stream_pull_address: String,
+    pub stream_push_address: Vec<String>,
+    pub panoramic: bool,
+    pub interval_millis: i64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct AuthorLiveStatus {
+    pub result: i32,
+    pub data: AuthorLiveStatusData,
+    pub host: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthorLiveStatusData {
+    pub live_id: String,
+    pub stream_name: String,
+    pub title: String,
+    pub live_cover: String,
+    pub live_start_time: i64,
+    pub panoramic: bool,
+    pub biz_unit: String,
+    pub biz_custom_data: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct StartLive {
+    pub result: i32,
+    pub data: StartLiveData,
+    pub host: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartLiveData {
+    pub live_id: String,
+    pub enable_salt_live_id: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct StopLive {
+    pub result: i32,
+    pub data: StopLiveData,
+    pub host: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StopLiveData {
+    pub duration_ms: i64,
+    pub end_reason: String,
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LiveList {
@@ -292,6 +499,24 @@ pub struct LiveList {
     pub channel_filters: ChannelFilters,
 }
 
+impl LiveList {
+    pub fn live_types(&self) -> Vec<LiveType> {
+        let mut types: Vec<LiveType> = Vec::new();
+        for t in self
+            .live_list
+            .iter()
+            .filter_map(|l| l.live_data.as_ref()?.live_type.as_ref())
+        {
+            if !types.iter().any(|e| e.id == t.id) {
+                types.push(t.clone());
+            }
+        }
+        types.sort_unstable_by_key(|t| (t.category_id, t.id));
+
+        types
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ChannelListData {
@@ -0,0 +1,342 @@
+//! Retries failed requests with exponential backoff and limits the rate of requests to each host.
+
+use futures_timer::Delay;
+use pretend::{
+    client::{async_trait, Bytes, Client, Method},
+    HeaderMap, Response, StatusCode, Url,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+/// Marks a POST request as idempotent so it can be retried, removed before sending the request.
+///
+/// GET, HEAD, PUT, DELETE and OPTIONS requests are always idempotent.
+pub const IDEMPOTENT_HEADER: &str = "x-acfunlive-idempotent";
+
+/// Whether the request is idempotent, removes the marker header from the request.
+#[inline]
+fn take_idempotent(method: &Method, headers: &mut HeaderMap) -> bool {
+    let marked = headers.remove(IDEMPOTENT_HEADER).is_some();
+    match *method {
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS => true,
+        Method::POST => marked,
+        _ => false,
+    }
+}
+
+/// Idempotent requests are retried on timeouts, connection errors, 5xx and 429 responses.
+///
+/// Timeouts when reading the response body are retried too.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct RetryPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: u32,
+    pub max_retries: u32,
+}
+
+impl Default for RetryPolicy {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2,
+            max_retries: 3,
+        }
+    }
+}
+
+impl RetryPolicy {
+    #[inline]
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.multiplier
+            .checked_pow(attempt)
+            .and_then(|m| self.initial_backoff.checked_mul(m))
+            .map_or(self.max_backoff, |d| d.min(self.max_backoff))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct HostLimit {
+    interval: Duration,
+    next: Instant,
+}
+
+/// Limits the minimum interval between requests to each host, clones share the same limits.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimiter(Arc<Mutex<HashMap<String, HostLimit>>>);
+
+impl RateLimiter {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the minimum interval between requests to the host, `None` removes the limit.
+    pub fn set_interval(&self, host: impl Into<String>, interval: Option<Duration>) -> &Self {
+        let mut limits = self.0.lock().expect("the rate limiter was poisoned");
+        let host = host.into();
+        match interval {
+            Some(interval) => {
+                let next = limits.get(&host).map_or_else(Instant::now, |l| l.next);
+                let _ = limits.insert(host, HostLimit { interval, next });
+            }
+            None => {
+                let _ = limits.remove(&host);
+            }
+        }
+        self
+    }
+
+    #[inline]
+    pub fn interval(&self, host: &str) -> Option<Duration> {
+        self.0
+            .lock()
+            .expect("the rate limiter was poisoned")
+            .get(host)
+            .map(|l| l.interval)
+    }
+
+    /// Reserves the next slot of the host and returns how long to wait for it.
+    fn reserve(&self, host: &str) -> Option<Duration> {
+        let mut limits = self.0.lock().expect("the rate limiter was poisoned");
+        let limit = limits.get_mut(host)?;
+        let now = Instant::now();
+        let slot = limit.next.max(now);
+        limit.next = slot + limit.interval;
+
+        Some(slot - now).filter(|d| !d.is_zero())
+    }
+
+    /// Waits until a request can be sent to the host.
+    pub async fn wait(&self, host: &str) {
+        if let Some(delay) = self.reserve(host) {
+            Delay::new(delay).await;
+        }
+    }
+}
+
+/// A `pretend` client which applies the retry policy and the rate limiter to requests.
+///
+/// Clones share the same retry policy and rate limiter.
+#[derive(Clone, Debug)]
+pub struct RetryClient<C> {
+    client: C,
+    policy: Arc<RwLock<Option<RetryPolicy>>>,
+    limiter: RateLimiter,
+}
+
+impl<C> RetryClient<C> {
+    /// Requests aren't retried by default.
+    #[inline]
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            policy: Arc::new(RwLock::new(None)),
+            limiter: RateLimiter::new(),
+        }
+    }
+
+    #[inline]
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+
+    #[inline]
+    pub fn policy(&self) -> Option<RetryPolicy> {
+        *self.policy.read().expect("the retry policy was poisoned")
+    }
+
+    #[inline]
+    pub fn set_policy(&self, policy: Option<RetryPolicy>) -> &Self {
+        *self.policy.write().expect("the retry policy was poisoned") = policy;
+        self
+    }
+
+    #[inline]
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+}
+
+#[async_trait]
+impl<C> Client for RetryClient<C>
+where
+    C: Client + Send + Sync,
+{
+    async fn execute(
+        &self,
+        method: Method,
+        url: Url,
+        mut headers: HeaderMap,
+        body: Option<Bytes>,
+    ) -> pretend::Result<Response<Bytes>> {
+        let idempotent = take_idempotent(&method, &mut headers);
+        let policy = self.policy().filter(|_| idempotent);
+        let host = url.host_str().unwrap_or_default().to_string();
+        let mut attempt = 0;
+        loop {
+            self.limiter.wait(&host).await;
+            let result = self
+                .client
+                .execute(method.clone(), url.clone(), headers.clone(), body.clone())
+                .await;
+            let retryable = match &result {
+                Ok(resp) => {
+                    resp.status().is_server_error()
+                        || *resp.status() == StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(pretend::Error::Response(_) | pretend::Error::Body(_)) => true,
+                Err(_) => false,
+            };
+            match policy {
+                Some(policy) if retryable && attempt < policy.max_retries => {
+                    Delay::new(policy.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[derive(Clone, Debug, Default)]
+    struct FlakyClient {
+        failures: u32,
+        body_errors: bool,
+        requests: Arc<AtomicU32>,
+        marked: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl Client for FlakyClient {
+        async fn execute(
+            &self,
+            _method: Method,
+            _url: Url,
+            headers: HeaderMap,
+            _body: Option<Bytes>,
+        ) -> pretend::Result<Response<Bytes>> {
+            if headers.contains_key(IDEMPOTENT_HEADER) {
+                let _ = self.marked.fetch_add(1, Ordering::SeqCst);
+            }
+            let n = self.requests.fetch_add(1, Ordering::SeqCst);
+            if self.body_errors && n < self.failures {
+                return Err(pretend::Error::body(std::io::Error::from(
+                    std::io::ErrorKind::TimedOut,
+                )));
+            }
+            let status = if n < self.failures {
+                StatusCode::SERVICE_UNAVAILABLE
+            } else {
+                StatusCode::OK
+            };
+            Ok(Response::new(
+                status,
+                HeaderMap::new(),
+                Bytes::from_static(b"{}"),
+            ))
+        }
+    }
+
+    async fn send(
+        client: &RetryClient<FlakyClient>,
+        method: Method,
+        url: &Url,
+        idempotent: bool,
+    ) -> pretend::Result<Response<Bytes>> {
+        let mut headers = HeaderMap::new();
+        if idempotent {
+            let _ = headers.insert(
+                IDEMPOTENT_HEADER,
+                "1".parse().expect("invalid header value"),
+            );
+        }
+        client.execute(method, url.clone(), headers, None).await
+    }
+
+    #[tokio::test]
+    async fn test_retry_client() -> pretend::Result<()> {
+        let gift_list = Url::parse("https://api.kuaishouzt.com/rest/zt/live/web/gift/list")
+            .map_err(pretend::Error::request)?;
+        let comment =
+            Url::parse("https://api.kuaishouzt.com/rest/zt/live/web/audience/action/comment")
+                .map_err(pretend::Error::request)?;
+
+        let flaky = FlakyClient {
+            failures: 2,
+            ..Default::default()
+        };
+        let client = RetryClient::new(flaky.clone());
+        assert_eq!(
+            *send(&client, Method::GET, &gift_list, false)
+                .await?
+                .status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        flaky.requests.store(0, Ordering::SeqCst);
+        let _ = client.set_policy(Some(RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        }));
+        assert_eq!(
+            *send(&client, Method::POST, &gift_list, true)
+                .await?
+                .status(),
+            StatusCode::OK
+        );
+        assert_eq!(flaky.requests.load(Ordering::SeqCst), 3);
+        assert_eq!(flaky.marked.load(Ordering::SeqCst), 0);
+        flaky.requests.store(0, Ordering::SeqCst);
+        assert_eq!(
+            *send(&client, Method::POST, &comment, false).await?.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(flaky.requests.load(Ordering::SeqCst), 1);
+
+        // timeouts when reading the body are retried
+        let flaky = FlakyClient {
+            failures: 1,
+            body_errors: true,
+            ..Default::default()
+        };
+        let client = RetryClient::new(flaky.clone());
+        let _ = client.set_policy(Some(RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        }));
+        assert_eq!(
+            *send(&client, Method::POST, &gift_list, true)
+                .await?
+                .status(),
+            StatusCode::OK
+        );
+        assert_eq!(flaky.requests.load(Ordering::SeqCst), 2);
+
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(10), Duration::from_secs(10));
+
+        let client = RetryClient::new(FlakyClient::default());
+        let _ = client
+            .rate_limiter()
+            .set_interval("api.kuaishouzt.com", Some(Duration::from_millis(50)));
+        let start = Instant::now();
+        for _ in 0..3 {
+            let _ = send(&client, Method::POST, &comment, false).await?;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+//! Persists `ApiToken` so that the login session can be restored without signing in again.
+
+use crate::{client::ApiToken, Result};
+use async_trait::async_trait;
+use blocking::unblock;
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+#[async_trait]
+pub trait TokenStore: fmt::Debug + Send + Sync {
+    /// Loads the saved token, returns `None` if no token was saved.
+    async fn load(&self) -> Result<Option<ApiToken>>;
+
+    async fn save(&self, token: &ApiToken) -> Result<()>;
+
+    async fn clear(&self) -> Result<()>;
+}
+
+/// Keeps the token in memory, clones share the same token.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryTokenStore(Arc<Mutex<Option<ApiToken>>>);
+
+impl MemoryTokenStore {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn with_token(token: ApiToken) -> Self {
+        Self(Arc::new(Mutex::new(Some(token))))
+    }
+
+    #[inline]
+    pub fn token(&self) -> Option<ApiToken> {
+        self.0.lock().expect("the token store was poisoned").clone()
+    }
+}
+
+#[async_trait]
+impl TokenStore for MemoryTokenStore {
+    #[inline]
+    async fn load(&self) -> Result<Option<ApiToken>> {
+        Ok(self.token())
+    }
+
+    #[inline]
+    async fn save(&self, token: &ApiToken) -> Result<()> {
+        *self.0.lock().expect("the token store was poisoned") = Some(token.clone());
+        Ok(())
+    }
+
+    #[inline]
+    async fn clear(&self) -> Result<()> {
+        *self.0.lock().expect("the token store was poisoned") = None;
+        Ok(())
+    }
+}
+
+/// Saves the token as JSON in a file, the file is read and written in a blocking thread pool.
+///
+/// The token contains the cookies of the user, so the file is created with mode 0600 on unix.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct FileTokenStore {
+    path: PathBuf,
+}
+
+impl FileTokenStore {
+    #[inline]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[async_trait]
+impl TokenStore for FileTokenStore {
+    async fn load(&self) -> Result<Option<ApiToken>> {
+        let path = self.path.clone();
+        match unblock(move || fs::read(path)).await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn save(&self, token: &ApiToken) -> Result<()> {
+        let path = self.path.clone();
+        let data = serde_json::to_vec_pretty(token)?;
+        Ok(unblock(move || write_private(&path, &data)).await?)
+    }
+
+    async fn clear(&self) -> Result<()> {
+        let path = self.path.clone();
+        match unblock(move || fs::remove_file(path)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Writes the file which is only readable and writable by the owner on unix.
+///
+/// The mode only applies when the file is created, an existing file keeps its permissions.
+fn write_private(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    let _ = options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        let _ = options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn token() -> ApiToken {
+        ApiToken {
+            user_id: 10000,
+            security_key: "security_key".to_string(),
+            service_token: "service_token".to_string(),
+            device_id: "device_id".to_string(),
+            cookies: Some("auth_key=10000".to_string()),
+        }
+    }
+
+    async fn roundtrip(store: &dyn TokenStore) -> Result<()> {
+        assert_eq!(store.load().await?, None);
+        store.save(&token()).await?;
+        assert_eq!(store.load().await?, Some(token()));
+        store.clear().await?;
+        assert_eq!(store.load().await?, None);
+        store.clear().await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_token_store() -> Result<()> {
+        roundtrip(&MemoryTokenStore::new()).await?;
+        assert_eq!(MemoryTokenStore::with_token(token()).token(), Some(token()));
+
+        let path = env::temp_dir().join(format!("acfunliveapi_token_{}.json", std::process::id()));
+        roundtrip(&FileTokenStore::new(&path)).await?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let store = FileTokenStore::new(&path);
+            store.save(&token()).await?;
+            assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+            store.clear().await?;
+        }
+
+        Ok(())
+    }
+}
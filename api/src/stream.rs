@@ -0,0 +1,231 @@
+//! Selects the stream URL of a live from `StreamInfo`.
+
+use crate::{client::Stream, response::*};
+
+/// The quality selected among the representations of a manifest.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Quality {
+    /// The representation with `default_select`, or the highest one if there isn't.
+    Default,
+    Highest,
+    Lowest,
+    /// The highest representation whose bitrate isn't above the cap, or the lowest one if all are.
+    MaxBitrate(i32),
+}
+
+impl Default for Quality {
+    #[inline]
+    fn default() -> Self {
+        Self::Default
+    }
+}
+
+/// Picks a manifest and a representation in it.
+///
+/// Hidden representations are skipped unless `include_hidden()` is set.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct StreamSelector {
+    quality: Quality,
+    free_traffic_cdn: Option<bool>,
+    media_type: Option<String>,
+    include_hidden: bool,
+    adaptive: bool,
+}
+
+impl StreamSelector {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn quality(mut self, quality: Quality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Prefers the manifest on the free traffic CDN or the normal one, the first manifest is
+    /// used if there isn't such a manifest.
+    #[inline]
+    pub fn free_traffic_cdn(mut self, free_traffic_cdn: bool) -> Self {
+        self.free_traffic_cdn = Some(free_traffic_cdn);
+        self
+    }
+
+    /// Only selects representations of the media type, e.g. `flv`.
+    #[inline]
+    pub fn media_type(mut self, media_type: impl Into<String>) -> Self {
+        self.media_type = Some(media_type.into());
+        self
+    }
+
+    #[inline]
+    pub fn include_hidden(mut self, include_hidden: bool) -> Self {
+        self.include_hidden = include_hidden;
+        self
+    }
+
+    /// Only selects representations which can be switched automatically, manifests with
+    /// `hide_auto` are skipped.
+    #[inline]
+    pub fn adaptive(mut self, adaptive: bool) -> Self {
+        self.adaptive = adaptive;
+        self
+    }
+
+    fn usable(&self, manifest: &LiveAdaptiveManifest) -> bool {
+        !(self.adaptive && manifest.hide_auto)
+            && manifest
+                .adaptation_set
+                .representation
+                .iter()
+                .any(|r| self.is_candidate(r))
+    }
+
+    fn is_candidate(&self, r: &Representation) -> bool {
+        (self.include_hidden || !r.hidden)
+            && (!self.adaptive || r.enable_adaptive)
+            && match self.media_type.as_ref() {
+                Some(t) => r.media_type.eq_ignore_ascii_case(t),
+                None => true,
+            }
+    }
+
+    /// The representations in the manifest which can be selected.
+    pub fn candidates<'a>(&self, manifest: &'a LiveAdaptiveManifest) -> Vec<&'a Representation> {
+        manifest
+            .adaptation_set
+            .representation
+            .iter()
+            .filter(|r| self.is_candidate(r))
+            .collect()
+    }
+
+    /// Picks the manifest which has selectable representations.
+    pub fn select_manifest<'a>(&self, info: &'a StreamInfo) -> Option<&'a LiveAdaptiveManifest> {
+        let mut manifests = info
+            .live_adaptive_manifest
+            .iter()
+            .filter(|m| self.usable(m));
+        match self.free_traffic_cdn {
+            Some(free) => {
+                let first = manifests.clone().next();
+                manifests.find(|m| m.free_traffic_cdn == free).or(first)
+            }
+            None => manifests.next(),
+        }
+    }
+
+    /// Picks the representation in the manifest with the quality.
+    pub fn select_representation<'a>(
+        &self,
+        manifest: &'a LiveAdaptiveManifest,
+    ) -> Option<&'a Representation> {
+        let highest = |r: &&Representation| (r.bitrate, r.level);
+        let candidates = self.candidates(manifest).into_iter();
+        match self.quality {
+            Quality::Default => {
+                let default = candidates.clone().find(|r| r.default_select);
+                default.or_else(|| candidates.max_by_key(highest))
+            }
+            Quality::Highest => candidates.max_by_key(highest),
+            Quality::Lowest => candidates.min_by_key(highest),
+            Quality::MaxBitrate(max) => {
+                let under = candidates
+                    .clone()
+                    .filter(|r| r.bitrate <= max)
+                    .max_by_key(highest);
+                under.or_else(|| candidates.min_by_key(highest))
+            }
+        }
+    }
+
+    #[inline]
+    pub fn select<'a>(&self, info: &'a StreamInfo) -> Option<&'a Representation> {
+        self.select_representation(self.select_manifest(info)?)
+    }
+
+    #[inline]
+    pub fn select_stream(&self, info: &StreamInfo) -> Option<Stream> {
+        self.select(info).map(Stream::from)
+    }
+}
+
+impl From<&Representation> for Stream {
+    #[inline]
+    fn from(r: &Representation) -> Self {
+        Self {
+            url: r.url.clone(),
+            bitrate: r.bitrate,
+            quality_type: r.quality_type.clone(),
+            quality_name: r.name.clone(),
+            media_type: r.media_type.clone(),
+            level: r.level,
+            hidden: r.hidden,
+            default_select: r.default_select,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mock::LIVE_INFO, Result};
+
+    #[test]
+    fn test_stream_selector() -> Result<()> {
+        let info = serde_json::from_str::<LiveInfo>(LIVE_INFO)?
+            .data
+            .video_play_res;
+        let quality = |selector: StreamSelector| {
+            selector
+                .select(&info)
+                .map(|r| r.quality_type.as_str())
+                .unwrap_or_default()
+        };
+        assert_eq!(quality(StreamSelector::new()), "HIGH");
+        assert_eq!(
+            quality(StreamSelector::new().quality(Quality::Highest)),
+            "SUPER"
+        );
+        assert_eq!(
+            quality(
+                StreamSelector::new()
+                    .quality(Quality::Highest)
+                    .include_hidden(true)
+            ),
+            "BLUE_RAY"
+        );
+        assert_eq!(
+            quality(StreamSelector::new().quality(Quality::Lowest)),
+            "STANDARD"
+        );
+        assert_eq!(
+            quality(StreamSelector::new().quality(Quality::MaxBitrate(3000))),
+            "HIGH"
+        );
+        assert_eq!(
+            quality(StreamSelector::new().quality(Quality::MaxBitrate(500))),
+            "STANDARD"
+        );
+        assert_eq!(quality(StreamSelector::new().media_type("hls")), "");
+
+        let url = |selector: StreamSelector| {
+            selector
+                .select_stream(&info)
+                .map(|s| s.url)
+                .unwrap_or_default()
+        };
+        assert!(url(StreamSelector::new()).starts_with("https://mock.cdn/"));
+        assert!(
+            url(StreamSelector::new().free_traffic_cdn(true)).starts_with("https://free.mock.cdn/")
+        );
+        // the free traffic CDN manifest hides the automatic switching
+        assert!(
+            url(StreamSelector::new().free_traffic_cdn(true).adaptive(true))
+                .starts_with("https://mock.cdn/")
+        );
+
+        Ok(())
+    }
+}
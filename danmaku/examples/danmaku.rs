@@ -4,7 +4,7 @@ use acfunliveapi::{
 };
 use acfunlivedanmaku::{client::*, danmaku::*, Result};
 use futures::StreamExt;
-use std::{collections::HashMap, convert::TryInto, env, time::Duration};
+use std::{collections::HashMap, convert::TryInto, env, sync::Arc, time::Duration};
 use tokio::time::timeout;
 
 #[tokio::main]
@@ -31,12 +31,17 @@ async fn main() -> Result<()> {
         .map(|g| (g.gift_id, g))
         .collect();
 
-    let mut client = DanmakuClient::default_client(api_client.try_into()?).await?;
+    let mut client = DanmakuClient::default_client((&api_client).try_into()?).await?;
+    let _ = client
+        .set_reconnect_policy(Some(ReconnectPolicy::default()))
+        .set_token_refresher(Some(Arc::new(api_client)));
     loop {
         match timeout(Duration::from_secs(10), client.next()).await {
             Ok(Some(Ok(Danmaku::ActionSignal(action)))) => handle_action(action, &gifts),
             Ok(Some(Ok(Danmaku::StateSignal(state)))) => handle_state(state),
             Ok(Some(Ok(Danmaku::NotifySignal(notify)))) => handle_notify(notify),
+            Ok(Some(Ok(Danmaku::StatusChanged(status)))) => println!("live status: {:?}", status),
+            Ok(Some(Ok(Danmaku::Reconnected))) => println!("reconnected"),
             Ok(Some(Err(e))) => {
                 println!("error: {}", e);
                 break;
@@ -154,6 +159,22 @@ fn handle_state(state: Vec<StateSignal>) {
             StateSignal::AuthorChatReady(d) => println!("{:?}", d),
             StateSignal::AuthorChatEnd(d) => println!("{:?}", d),
             StateSignal::AuthorChatChangeSoundConfig(d) => println!("{:?}", d),
+            StateSignal::PkInvitation(d) => println!("{:?}", d),
+            StateSignal::PkAccept(d) => println!("{:?}", d),
+            StateSignal::PkReady(d) => println!("{:?}", d),
+            StateSignal::PkSoundConfigChanged(d) => println!("{:?}", d),
+            StateSignal::PkEnd(d) => println!("{:?}", d),
+            StateSignal::PkStatistic(d) => d.players().iter().for_each(|p| {
+                let user_info = p.user_info().cloned().unwrap_or_default();
+                println!(
+                    "PK score: {}({}): {}",
+                    user_info.nickname,
+                    user_info.user_id,
+                    p.score()
+                );
+            }),
+            StateSignal::WishSheet(d) => println!("{:?}", d),
+            StateSignal::FeatureStateSync(d) => println!("{:?}", d),
             StateSignal::Unknown(s) => {
                 println!("unknown state signal: {}", String::from_utf8_lossy(&s))
             }
@@ -167,6 +188,9 @@ fn handle_notify(notify: Vec<NotifySignal>) {
             NotifySignal::KickedOut(d) => println!("kicked out: {}", d.reason),
             NotifySignal::ViolationAlert(d) => println!("violation alert: {}", d.violation_content),
             NotifySignal::ManagerState(d) => println!("manager state: {:?}", d.state()),
+            NotifySignal::CoverAuditResult(d) => {
+                println!("cover audit result: {:?}", d.audit_status())
+            }
             NotifySignal::Unknown(s) => {
                 println!("unknown notify signal: {}", String::from_utf8_lossy(&s))
             }
@@ -1,10 +1,20 @@
-use crate::{danmaku::*, global::*, proto::*, websocket::*, Error, Result};
+use crate::{danmaku::*, global::*, proto::*, record::Recorder, websocket::*, Error, Result};
+use async_trait::async_trait;
 use asynchronous_codec::Framed;
-use futures::{ready, stream::FusedStream, SinkExt, Stream, StreamExt};
+use futures::{
+    future::{self, BoxFuture},
+    ready,
+    stream::FusedStream,
+    Future, SinkExt, Stream, StreamExt,
+};
+use futures_timer::Delay;
 use std::{
+    borrow::Cow,
     collections::VecDeque,
     convert::TryInto,
+    fmt,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
     time::{Duration, SystemTime},
 };
@@ -15,7 +25,7 @@ use acfunliveapi::{
     pretend,
 };
 #[cfg(feature = "api")]
-use std::{borrow::Cow, convert::TryFrom};
+use std::convert::TryFrom;
 
 #[cfg_attr(feature = "_serde", derive(::serde::Deserialize, ::serde::Serialize))]
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
@@ -104,6 +114,23 @@ impl DanmakuToken {
     }
 }
 
+#[async_trait]
+pub trait TokenRefresher: Send + Sync {
+    async fn refresh(&self, liver_uid: i64) -> Result<DanmakuToken>;
+}
+
+#[cfg(feature = "api")]
+#[async_trait]
+impl<C> TokenRefresher for ApiClient<C>
+where
+    C: pretend::client::Client + Send + Sync,
+{
+    #[inline]
+    async fn refresh(&self, liver_uid: i64) -> Result<DanmakuToken> {
+        DanmakuToken::from_api_client(self, liver_uid).await
+    }
+}
+
 #[cfg(feature = "api")]
 impl<C> TryFrom<ApiClient<C>> for DanmakuToken {
     type Error = Error;
@@ -128,40 +155,120 @@ impl<C> TryFrom<&ApiClient<C>> for DanmakuToken {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: u32,
+    pub max_retries: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2,
+            max_retries: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    #[inline]
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.multiplier
+            .checked_pow(attempt)
+            .and_then(|m| self.initial_delay.checked_mul(m))
+            .map_or(self.max_delay, |d| d.min(self.max_delay))
+    }
+
+    #[inline]
+    pub fn can_retry(&self, attempt: u32) -> bool {
+        !matches!(self.max_retries, Some(max) if attempt >= max)
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum ClientState {
     BeforeRegister,
     Registering,
     Registered,
+    Refreshing,
+    Reconnecting,
     Closing,
     Closed,
 }
 
+/// A boxed future kept in a state machine which is polled by hand.
+pub(crate) struct Pending<T>(pub(crate) BoxFuture<'static, T>);
+
+impl<T> fmt::Debug for Pending<T> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Pending")
+    }
+}
+
+#[derive(Clone)]
+struct Refresher(Arc<dyn TokenRefresher>);
+
+impl fmt::Debug for Refresher {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Refresher")
+    }
+}
+
 #[cfg(feature = "default_ws_client")]
 pub type DefaultDanmakuClient = DanmakuClient<WebSocketClient>;
 
 #[derive(Debug)]
-pub struct DanmakuClient<W> {
+pub struct DanmakuClient<W: WebSocket> {
     client: Framed<W, DanmakuProto>,
+    server: String,
     state: ClientState,
     message: VecDeque<SendMessage>,
     interval: Option<Duration>,
     time: SystemTime,
     heartbeat_seq_id: i64,
+    reconnect: Option<ReconnectPolicy>,
+    reconnect_attempt: u32,
+    reconnect_delay: Option<Delay>,
+    connecting: Option<Pending<std::result::Result<W, W::Error>>>,
+    refresher: Option<Refresher>,
+    refreshing: Option<Pending<Result<DanmakuToken>>>,
 }
 
 impl<W: WebSocket> DanmakuClient<W> {
     #[inline]
     pub async fn new(token: DanmakuToken) -> std::result::Result<Self, W::Error> {
+        Self::with_server(token, DANMAKU_SERVER).await
+    }
+
+    /// Connects to the specified danmaku server instead of AcFun's, e.g. a local mock server.
+    pub async fn with_server<'a>(
+        token: DanmakuToken,
+        server: impl Into<Cow<'a, str>>,
+    ) -> std::result::Result<Self, W::Error> {
         if token.is_valid() {
-            match W::connect(DANMAKU_SERVER).await {
+            let server = server.into().into_owned();
+            match W::connect(server.as_str()).await {
                 Ok(client) => Ok(Self {
                     client: Framed::new(client, token.try_into()?),
+                    server,
                     state: ClientState::BeforeRegister,
                     message: VecDeque::new(),
                     interval: None,
                     time: SystemTime::now(),
                     heartbeat_seq_id: 0,
+                    reconnect: None,
+                    reconnect_attempt: 0,
+                    reconnect_delay: None,
+                    connecting: None,
+                    refresher: None,
+                    refreshing: None,
                 }),
                 Err(e) => Err(e),
             }
@@ -185,9 +292,121 @@ impl<W: WebSocket> DanmakuClient<W> {
         &self.client.codec().live_id
     }
 
+    #[inline]
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    #[inline]
+    pub fn set_reconnect_policy(&mut self, policy: Option<ReconnectPolicy>) -> &mut Self {
+        self.reconnect = policy;
+        self
+    }
+
+    #[inline]
+    pub const fn reconnect_policy(&self) -> Option<&ReconnectPolicy> {
+        self.reconnect.as_ref()
+    }
+
+    /// Sets the refresher which gets new tickets when all tickets are invalid, the client returns
+    /// `Error::TicketsExhausted` and closes in that case if there is no refresher.
+    #[inline]
+    pub fn set_token_refresher(&mut self, refresher: Option<Arc<dyn TokenRefresher>>) -> &mut Self {
+        self.refresher = refresher.map(Refresher);
+        self
+    }
+
+    #[inline]
+    pub fn has_token_refresher(&self) -> bool {
+        self.refresher.is_some()
+    }
+
+    /// Records every `DownstreamPayload` received from the danmaku server.
+    ///
+    /// The records are written while the client is polled, and all of them are written before the
+    /// stream ends. If writing the recording failed, the recorder is dropped, `Error::RecordError`
+    /// is returned once and the client keeps receiving danmaku.
+    #[inline]
+    pub fn set_recorder(&mut self, recorder: Option<Recorder>) -> &mut Self {
+        self.client.codec_mut().recorder = recorder;
+        self
+    }
+
+    #[inline]
+    pub fn recorder(&self) -> Option<&Recorder> {
+        self.client.codec().recorder.as_ref()
+    }
+
+    /// Closes the connection and waits until the records of the recorder are written.
     #[inline]
     pub async fn close(&mut self) -> Result<()> {
-        self.client.close().await
+        self.state = ClientState::Closed;
+        self.client.close().await?;
+        future::poll_fn(|cx| self.poll_recorder(cx)).await
+    }
+
+    /// Writes the buffered records, the recorder is dropped if it failed.
+    fn poll_recorder(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let recorder = match self.client.codec().recorder.clone() {
+            Some(recorder) => recorder,
+            None => return Poll::Ready(Ok(())),
+        };
+        let result = ready!(recorder.poll_flush(cx));
+        if let Err(e) = &result {
+            log::trace!(
+                "failed to record the danmaku session, stop recording: {}",
+                e
+            );
+            self.client.codec_mut().recorder = None;
+        }
+
+        Poll::Ready(result)
+    }
+
+    fn schedule_reconnect(&mut self) -> bool {
+        match self.reconnect {
+            Some(policy) if policy.can_retry(self.reconnect_attempt) => {
+                let delay = policy.delay(self.reconnect_attempt);
+                log::trace!(
+                    "reconnect to the danmaku server in {:?}, attempt {}",
+                    delay,
+                    self.reconnect_attempt + 1
+                );
+                self.reconnect_attempt += 1;
+                self.reconnect_delay = Some(Delay::new(delay));
+                self.state = ClientState::Reconnecting;
+                true
+            }
+            _ => {
+                self.state = ClientState::Closed;
+                false
+            }
+        }
+    }
+
+    #[inline]
+    fn reconnect_on_error(&mut self, error: Error) -> Option<Error> {
+        if let Error::IoError(e) = &error {
+            if self.reconnect.is_some() {
+                log::trace!("the WebSocket connection was broken: {}", e);
+                if self.schedule_reconnect() {
+                    return None;
+                }
+            }
+        }
+
+        Some(error)
+    }
+
+    fn reconnected(&mut self, ws: W) {
+        let mut codec = std::mem::take(self.client.codec_mut());
+        codec.reset();
+        self.client = Framed::new(ws, codec);
+        self.state = ClientState::BeforeRegister;
+        self.message.clear();
+        self.interval = None;
+        self.time = SystemTime::now();
+        self.heartbeat_seq_id = 0;
     }
 }
 
@@ -224,26 +443,64 @@ impl DanmakuClient<WebSocketClient> {
     }
 }
 
-impl<W: WebSocket> Stream for DanmakuClient<W> {
+impl<W: WebSocket + 'static> Stream for DanmakuClient<W> {
     type Item = std::result::Result<Danmaku, W::Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        macro_rules! try_conn {
+            ($result:expr) => {
+                match $result {
+                    Ok(v) => v,
+                    Err(e) => match self.reconnect_on_error(e) {
+                        Some(e) => return Poll::Ready(Some(Err(e.into()))),
+                        None => continue,
+                    },
+                }
+            };
+        }
+
+        // the stream ends after all records are written
+        macro_rules! end {
+            () => {{
+                if let Err(e) = ready!(self.poll_recorder(cx)) {
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+                return Poll::Ready(None);
+            }};
+        }
+
+        macro_rules! next_msg {
+            () => {
+                match ready!(self.client.poll_next_unpin(cx)) {
+                    Some(result) => try_conn!(result),
+                    None => {
+                        if self.schedule_reconnect() {
+                            continue;
+                        }
+                        end!()
+                    }
+                }
+            };
+        }
+
         loop {
+            if let Some(e) = self.client.codec_mut().record_error.take() {
+                return Poll::Ready(Some(Err(e.into())));
+            }
+            if let Poll::Ready(Err(e)) = self.poll_recorder(cx) {
+                return Poll::Ready(Some(Err(e.into())));
+            }
             match self.state {
                 ClientState::BeforeRegister => {
-                    ready!(self.client.poll_ready_unpin(cx))?;
+                    try_conn!(ready!(self.client.poll_ready_unpin(cx)));
                     self.client.start_send_unpin(SendMessage::RegisterRequest)?;
                     self.state = ClientState::Registering;
                 }
                 ClientState::Registering => {
-                    ready!(self.client.poll_flush_unpin(cx))?;
-                    let msg = if let Some(result) = ready!(self.client.poll_next_unpin(cx)) {
-                        result?
-                    } else {
-                        self.state = ClientState::Closed;
-                        return Poll::Ready(None);
-                    };
+                    try_conn!(ready!(self.client.poll_flush_unpin(cx)));
+                    let msg = next_msg!();
                     if msg == ReceiveMessage::RegisterResponse {
+                        self.reconnect_attempt = 0;
                         self.message.push_back(SendMessage::KeepAliveRequest);
                         self.message.push_back(SendMessage::ZtLiveCsEnterRoom);
                         self.state = ClientState::Registered;
@@ -271,20 +528,15 @@ impl<W: WebSocket> Stream for DanmakuClient<W> {
                         }
                     }
                     while !self.message.is_empty() {
-                        ready!(self.client.poll_ready_unpin(cx))?;
+                        try_conn!(ready!(self.client.poll_ready_unpin(cx)));
                         let msg = self
                             .message
                             .pop_front()
                             .expect("the message VecDeque is empty");
                         self.client.start_send_unpin(msg)?;
                     }
-                    ready!(self.client.poll_flush_unpin(cx))?;
-                    let msg = if let Some(result) = ready!(self.client.poll_next_unpin(cx)) {
-                        result?
-                    } else {
-                        self.state = ClientState::Closed;
-                        return Poll::Ready(None);
-                    };
+                    try_conn!(ready!(self.client.poll_flush_unpin(cx)));
+                    let msg = next_msg!();
                     match msg {
                         ReceiveMessage::Danmaku(danmaku) => return Poll::Ready(Some(Ok(danmaku))),
                         ReceiveMessage::RegisterResponse => {
@@ -300,11 +552,33 @@ impl<W: WebSocket> Stream for DanmakuClient<W> {
                             self.message.push_back(SendMessage::ZtLiveScMessage);
                             self.message.push_back(SendMessage::ZtLiveCsEnterRoom);
                         }
-                        ReceiveMessage::PushAndStop => {
+                        ReceiveMessage::TicketsExhausted => {
                             self.message.push_back(SendMessage::ZtLiveScMessage);
-                            self.message.push_back(SendMessage::ZtLiveCsUserExit);
-                            self.message.push_back(SendMessage::UnregisterRequest);
-                            self.state = ClientState::Closing;
+                            match self.refresher.clone() {
+                                Some(Refresher(refresher)) => {
+                                    log::trace!("all danmaku tickets are invalid, refresh the danmaku token");
+                                    let liver_uid = self.liver_uid();
+                                    self.refreshing = Some(Pending(Box::pin(async move {
+                                        refresher.refresh(liver_uid).await
+                                    })));
+                                    self.state = ClientState::Refreshing;
+                                }
+                                None => {
+                                    self.message.push_back(SendMessage::ZtLiveCsUserExit);
+                                    self.message.push_back(SendMessage::UnregisterRequest);
+                                    self.state = ClientState::Closing;
+                                    return Poll::Ready(Some(Err(Error::TicketsExhausted.into())));
+                                }
+                            }
+                        }
+                        ReceiveMessage::StatusChanged(status) => {
+                            self.message.push_back(SendMessage::ZtLiveScMessage);
+                            if status.is_ended() {
+                                self.message.push_back(SendMessage::ZtLiveCsUserExit);
+                                self.message.push_back(SendMessage::UnregisterRequest);
+                                self.state = ClientState::Closing;
+                            }
+                            return Poll::Ready(Some(Ok(Danmaku::StatusChanged(status))));
                         }
                         ReceiveMessage::Stop => {
                             self.message.push_back(SendMessage::ZtLiveCsUserExit);
@@ -316,6 +590,51 @@ impl<W: WebSocket> Stream for DanmakuClient<W> {
                         }
                     }
                 }
+                ClientState::Refreshing => {
+                    let result = ready!(self
+                        .refreshing
+                        .as_mut()
+                        .expect("no refreshing future when refreshing the token")
+                        .0
+                        .as_mut()
+                        .poll(cx));
+                    self.refreshing = None;
+                    if let Err(e) =
+                        result.and_then(|token| self.client.codec_mut().update_tickets(token))
+                    {
+                        self.message.push_back(SendMessage::ZtLiveCsUserExit);
+                        self.message.push_back(SendMessage::UnregisterRequest);
+                        self.state = ClientState::Closing;
+                        return Poll::Ready(Some(Err(e.into())));
+                    }
+                    self.state = ClientState::Registered;
+                    self.message.push_back(SendMessage::ZtLiveCsEnterRoom);
+                }
+                ClientState::Reconnecting => {
+                    if let Some(delay) = self.reconnect_delay.as_mut() {
+                        ready!(Pin::new(delay).poll(cx));
+                        self.reconnect_delay = None;
+                        self.connecting = Some(Pending(W::connect(self.server.clone())));
+                    }
+                    let result = ready!(self
+                        .connecting
+                        .as_mut()
+                        .expect("no connecting future when reconnecting")
+                        .0
+                        .as_mut()
+                        .poll(cx));
+                    self.connecting = None;
+                    match result {
+                        Ok(ws) => {
+                            self.reconnected(ws);
+                            return Poll::Ready(Some(Ok(Danmaku::Reconnected)));
+                        }
+                        Err(e) => {
+                            let _ = self.schedule_reconnect();
+                            return Poll::Ready(Some(Err(e)));
+                        }
+                    }
+                }
                 ClientState::Closing => {
                     while !self.message.is_empty() {
                         ready!(self.client.poll_ready_unpin(cx))?;
@@ -327,28 +646,279 @@ impl<W: WebSocket> Stream for DanmakuClient<W> {
                     }
                     ready!(self.client.poll_close_unpin(cx))?;
                     self.state = ClientState::Closed;
-                    return Poll::Ready(None);
                 }
-                ClientState::Closed => return Poll::Ready(None),
+                ClientState::Closed => end!(),
             }
         }
     }
 }
 
-impl<W: WebSocket> FusedStream for DanmakuClient<W> {
+impl<W: WebSocket + 'static> FusedStream for DanmakuClient<W> {
     #[inline]
     fn is_terminated(&self) -> bool {
         self.state == ClientState::Closed
     }
 }
 
-#[cfg(all(feature = "api", feature = "default_ws_client"))]
+#[cfg(feature = "default_ws_client")]
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{acproto, mock::*};
+    use futures::TryStreamExt;
+    use prost::Message;
     use std::env;
+    use tokio::time::timeout;
+
+    #[cfg(feature = "api")]
     use tokio::{select, time::sleep};
 
+    fn comment(content: &str) -> acproto::CommonActionSignalComment {
+        acproto::CommonActionSignalComment {
+            content: content.to_string(),
+            send_time_ms: 1_600_000_000_000,
+            user_info: Some(acproto::ZtLiveUserInfo {
+                user_id: 2,
+                nickname: "user".to_string(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn live_closed() -> acproto::ZtLiveScStatusChanged {
+        acproto::ZtLiveScStatusChanged {
+            r#type: acproto::zt_live_sc_status_changed::Type::LiveClosed.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reconnect_policy() {
+        let mut policy = ReconnectPolicy::default();
+        assert_eq!(policy.delay(0), Duration::from_secs(1));
+        assert_eq!(policy.delay(3), Duration::from_secs(8));
+        assert_eq!(policy.delay(10), Duration::from_secs(60));
+        assert_eq!(policy.delay(100), Duration::from_secs(60));
+        assert!(policy.can_retry(100));
+        policy.max_retries = Some(3);
+        assert!(policy.can_retry(2));
+        assert!(!policy.can_retry(3));
+    }
+
+    #[tokio::test]
+    async fn test_mock_danmaku() -> Result<()> {
+        let server = MockDanmakuServer::bind().await?;
+        let url = server.url();
+        let mut token = server.token();
+        token.tickets.insert(0, "invalid_ticket".to_string());
+        let server = server
+            .push_action_signal(COMMENT, comment("first").encode_to_vec())
+            .push_action_signal(COMMENT, comment("second").encode_to_vec())
+            .push_status_changed(live_closed())
+            .spawn();
+
+        let client = DefaultDanmakuClient::with_server(token, url).await?;
+        let danmaku: Vec<Danmaku> = timeout(Duration::from_secs(10), client.try_collect())
+            .await
+            .expect("timeout when getting danmaku from the mock server")?;
+        assert_eq!(
+            danmaku,
+            vec![
+                Danmaku::ActionSignal(vec![ActionSignal::Comment(comment("first"))]),
+                Danmaku::ActionSignal(vec![ActionSignal::Comment(comment("second"))]),
+                Danmaku::StatusChanged(LiveStatus::Closed),
+            ]
+        );
+        server.abort();
+
+        Ok(())
+    }
+
+    struct MockRefresher {
+        token: DanmakuToken,
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TokenRefresher for MockRefresher {
+        async fn refresh(&self, _liver_uid: i64) -> Result<DanmakuToken> {
+            let _ = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(self.token.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_ticket_refresh() -> Result<()> {
+        let server = MockDanmakuServer::bind().await?;
+        let url = server.url();
+        let token = server.token();
+        let mut expired = token.clone();
+        expired.tickets = vec!["expired_1".to_string(), "expired_2".to_string()];
+        let server = server
+            .push_action_signal(COMMENT, comment("comment").encode_to_vec())
+            .push_status_changed(live_closed())
+            .spawn();
+
+        let refresher = Arc::new(MockRefresher {
+            token,
+            calls: Default::default(),
+        });
+        let mut client = DefaultDanmakuClient::with_server(expired.clone(), url.clone()).await?;
+        let _ = client.set_token_refresher(Some(refresher.clone()));
+        let danmaku: Vec<Danmaku> = timeout(Duration::from_secs(10), client.try_collect())
+            .await
+            .expect("timeout when getting danmaku from the mock server")?;
+        assert_eq!(refresher.calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(
+            danmaku,
+            vec![
+                Danmaku::ActionSignal(vec![ActionSignal::Comment(comment("comment"))]),
+                Danmaku::StatusChanged(LiveStatus::Closed),
+            ]
+        );
+
+        // without a refresher the client stops instead of cycling through the invalid tickets
+        let client = DefaultDanmakuClient::with_server(expired, url).await?;
+        let results: Vec<_> = timeout(Duration::from_secs(10), client.collect::<Vec<_>>())
+            .await
+            .expect("timeout when getting danmaku from the mock server");
+        assert!(
+            matches!(results.as_slice(), [Err(Error::TicketsExhausted)]),
+            "{:?}",
+            results
+        );
+        server.abort();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_reconnect() -> Result<()> {
+        let server = MockDanmakuServer::bind().await?;
+        let url = server.url();
+        let token = server.token();
+        let server = server
+            .drop_connections(2)
+            .push_action_signal(COMMENT, comment("comment").encode_to_vec())
+            .push_status_changed(live_closed())
+            .spawn();
+
+        let mut client = DefaultDanmakuClient::with_server(token, url).await?;
+        let _ = client.set_reconnect_policy(Some(ReconnectPolicy {
+            initial_delay: Duration::from_millis(10),
+            ..Default::default()
+        }));
+        let danmaku: Vec<Danmaku> = timeout(Duration::from_secs(10), client.try_collect())
+            .await
+            .expect("timeout when getting danmaku from the mock server")?;
+        assert_eq!(
+            danmaku,
+            vec![
+                Danmaku::Reconnected,
+                Danmaku::Reconnected,
+                Danmaku::ActionSignal(vec![ActionSignal::Comment(comment("comment"))]),
+                Danmaku::StatusChanged(LiveStatus::Closed),
+            ]
+        );
+        server.abort();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_record_and_replay() -> Result<()> {
+        use crate::record::{RecordReader, Replay};
+
+        let server = MockDanmakuServer::bind().await?;
+        let url = server.url();
+        let token = server.token();
+        let server = server
+            .push_action_signal(COMMENT, comment("comment").encode_to_vec())
+            .push_status_changed(live_closed())
+            .spawn();
+        let path = env::temp_dir().join(format!(
+            "acfunlivedanmaku_test_{}.record",
+            std::process::id()
+        ));
+
+        let mut client = DefaultDanmakuClient::with_server(token, url).await?;
+        let _ = client.set_recorder(Some(Recorder::create(&path)?));
+        let danmaku: Vec<Danmaku> = timeout(Duration::from_secs(10), client.try_collect())
+            .await
+            .expect("timeout when getting danmaku from the mock server")?;
+        server.abort();
+
+        let records = RecordReader::open(&path)?.collect::<Result<Vec<_>>>()?;
+        assert!(records.iter().any(|r| r.payload.command == REGISTER));
+        let replay: Vec<Danmaku> = Replay::open(&path)?.speed(None).try_collect().await?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(danmaku.len(), 2);
+        assert_eq!(replay, danmaku);
+
+        Ok(())
+    }
+
+    /// Accepts the record magic and fails afterwards like a full disk.
+    struct FullDisk(usize);
+
+    impl std::io::Write for FullDisk {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.0 == 0 {
+                return Err(std::io::Error::other("no space left on device"));
+            }
+            let len = buf.len().min(self.0);
+            self.0 -= len;
+            Ok(len)
+        }
+
+        #[inline]
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_record_error() -> Result<()> {
+        let server = MockDanmakuServer::bind().await?;
+        let url = server.url();
+        let token = server.token();
+        let server = server
+            .push_action_signal(COMMENT, comment("comment").encode_to_vec())
+            .push_status_changed(live_closed())
+            .spawn();
+
+        let mut client = DefaultDanmakuClient::with_server(token, url).await?;
+        let _ = client
+            .set_reconnect_policy(Some(ReconnectPolicy {
+                initial_delay: Duration::from_millis(10),
+                ..Default::default()
+            }))
+            .set_recorder(Some(Recorder::new(FullDisk(
+                crate::record::RECORD_MAGIC.len(),
+            ))?));
+        let results: Vec<Result<Danmaku>> =
+            timeout(Duration::from_secs(10), client.by_ref().collect())
+                .await
+                .expect("timeout when getting danmaku from the mock server");
+        server.abort();
+        assert!(client.recorder().is_none());
+        // the records are written off the executor, so the error may come after some danmaku
+        let (errors, danmaku): (Vec<_>, Vec<_>) = results.into_iter().partition(|r| r.is_err());
+        assert!(matches!(errors.as_slice(), [Err(Error::RecordError(_))]));
+        let danmaku = danmaku.into_iter().collect::<Result<Vec<_>>>()?;
+        assert!(matches!(
+            danmaku.as_slice(),
+            [
+                Danmaku::ActionSignal(_),
+                Danmaku::StatusChanged(LiveStatus::Closed)
+            ]
+        ));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "api")]
+    #[ignore = "needs the network and the LIVER_UID environment variable"]
     #[tokio::test]
     async fn test_danmaku() -> Result<()> {
         let liver_uid: i64 = env::var("LIVER_UID")
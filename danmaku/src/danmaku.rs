@@ -46,6 +46,42 @@ pub enum Danmaku {
     ActionSignal(Vec<ActionSignal>),
     StateSignal(Vec<StateSignal>),
     NotifySignal(Vec<NotifySignal>),
+    StatusChanged(LiveStatus),
+    /// The client reconnected to the danmaku server, some danmaku may be lost.
+    #[from(ignore)]
+    Reconnected,
+}
+
+#[cfg_attr(feature = "_serde", derive(::serde::Deserialize, ::serde::Serialize))]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum LiveStatus {
+    Closed,
+    NewLiveOpened,
+    UrlChanged,
+    Banned { reason: String },
+    Unknown(i32),
+}
+
+impl LiveStatus {
+    #[inline]
+    pub const fn is_ended(&self) -> bool {
+        matches!(self, LiveStatus::Closed | LiveStatus::Banned { .. })
+    }
+}
+
+impl From<acproto::ZtLiveScStatusChanged> for LiveStatus {
+    fn from(status: acproto::ZtLiveScStatusChanged) -> Self {
+        use acproto::zt_live_sc_status_changed::Type;
+        match status.r#type() {
+            Type::LiveClosed => LiveStatus::Closed,
+            Type::NewLiveOpened => LiveStatus::NewLiveOpened,
+            Type::LiveUrlChanged => LiveStatus::UrlChanged,
+            Type::LiveBanned => LiveStatus::Banned {
+                reason: status.banned_info.unwrap_or_default().ban_reason,
+            },
+            Type::Unknown => LiveStatus::Unknown(status.r#type),
+        }
+    }
 }
 
 #[cfg_attr(feature = "_serde", derive(::serde::Deserialize, ::serde::Serialize))]
@@ -97,16 +133,96 @@ pub enum StateSignal {
     AuthorChatReady(acproto::CommonStateSignalAuthorChatReady),
     AuthorChatEnd(acproto::CommonStateSignalAuthorChatEnd),
     AuthorChatChangeSoundConfig(acproto::CommonStateSignalAuthorChatChangeSoundConfig),
+    PkInvitation(acproto::CommonStateSignalPkInvitation),
+    PkAccept(acproto::CommonStateSignalPkAccept),
+    PkReady(acproto::CommonStateSignalPkReady),
+    PkSoundConfigChanged(acproto::CommonStateSignalPkSoundConfigChanged),
+    PkEnd(acproto::CommonStateSignalPkEnd),
+    PkStatistic(acproto::CommonStateSignalPkStatistic),
+    WishSheet(acproto::CommonStateSignalWishSheetCurrentState),
+    FeatureStateSync(acproto::CommonStateSignalFeatureStateSync),
     #[cfg_attr(feature = "_serde", serde(with = "serde_bytes"))]
     Unknown(Vec<u8>),
 }
 
+impl StateSignal {
+    /// The ID of the PK, `None` if it isn't a PK signal.
+    #[inline]
+    pub fn pk_id(&self) -> Option<&str> {
+        match self {
+            StateSignal::PkInvitation(s) => Some(&s.a),
+            StateSignal::PkAccept(s) => Some(&s.a),
+            StateSignal::PkReady(s) => Some(&s.a),
+            StateSignal::PkSoundConfigChanged(s) => Some(&s.a),
+            StateSignal::PkEnd(s) => Some(&s.a),
+            StateSignal::PkStatistic(s) => Some(&s.a),
+            _ => None,
+        }
+    }
+}
+
+// The field names of the PK protos are unknown, so only the fields whose meaning is clear get
+// accessors.
+
+impl acproto::CommonStateSignalPkInvitation {
+    /// The liver who sent the invitation.
+    #[inline]
+    pub fn inviter(&self) -> Option<&acproto::PkPlayerInfo> {
+        self.b.as_ref()
+    }
+}
+
+impl acproto::CommonStateSignalPkReady {
+    #[inline]
+    pub fn players(&self) -> &[acproto::PkPlayerInfo] {
+        &self.b
+    }
+}
+
+impl acproto::CommonStateSignalPkStatistic {
+    #[inline]
+    pub fn players(&self) -> &[acproto::PkPlayerStatistic] {
+        &self.j
+    }
+}
+
+impl acproto::PkPlayerInfo {
+    #[inline]
+    pub fn user_info(&self) -> Option<&acproto::ZtLiveUserInfo> {
+        self.a.as_ref()
+    }
+
+    #[inline]
+    pub fn live_id(&self) -> &str {
+        &self.b
+    }
+}
+
+impl acproto::PkPlayerStatistic {
+    #[inline]
+    pub fn user_info(&self) -> Option<&acproto::ZtLiveUserInfo> {
+        self.a.as_ref()
+    }
+
+    #[inline]
+    pub fn live_id(&self) -> &str {
+        &self.b
+    }
+
+    /// The PK score of the liver.
+    #[inline]
+    pub fn score(&self) -> i64 {
+        self.c
+    }
+}
+
 #[cfg_attr(feature = "_serde", derive(::serde::Deserialize, ::serde::Serialize))]
 #[derive(Clone, Debug, From, PartialEq)]
 pub enum NotifySignal {
     KickedOut(acproto::CommonNotifySignalKickedOut),
     ViolationAlert(acproto::CommonNotifySignalViolationAlert),
     ManagerState(acproto::CommonNotifySignalLiveManagerState),
+    CoverAuditResult(acproto::CommonNotifySignalCoverAuditResult),
     #[cfg_attr(feature = "_serde", serde(with = "serde_bytes"))]
     Unknown(Vec<u8>),
 }
@@ -190,6 +306,28 @@ pub(crate) fn state_signal(payload: &[u8]) -> Result<Vec<StateSignal>> {
                 item.payload.as_slice(),
             )?
             .into(),
+            PK_INVITATION => {
+                acproto::CommonStateSignalPkInvitation::decode(item.payload.as_slice())?.into()
+            }
+            PK_ACCEPT => {
+                acproto::CommonStateSignalPkAccept::decode(item.payload.as_slice())?.into()
+            }
+            PK_READY => acproto::CommonStateSignalPkReady::decode(item.payload.as_slice())?.into(),
+            PK_SOUND_CONFIG => {
+                acproto::CommonStateSignalPkSoundConfigChanged::decode(item.payload.as_slice())?
+                    .into()
+            }
+            PK_END => acproto::CommonStateSignalPkEnd::decode(item.payload.as_slice())?.into(),
+            PK_STATISTIC => {
+                acproto::CommonStateSignalPkStatistic::decode(item.payload.as_slice())?.into()
+            }
+            WISH_SHEET => {
+                acproto::CommonStateSignalWishSheetCurrentState::decode(item.payload.as_slice())?
+                    .into()
+            }
+            FEATURE_STATE_SYNC => {
+                acproto::CommonStateSignalFeatureStateSync::decode(item.payload.as_slice())?.into()
+            }
             _ => {
                 log::trace!("unknown state signal type: {}", item.signal_type);
                 item.payload.into()
@@ -214,6 +352,9 @@ pub(crate) fn notify_signal(payload: &[u8]) -> Result<Vec<NotifySignal>> {
             MANAGER_STATE => {
                 acproto::CommonNotifySignalLiveManagerState::decode(item.payload.as_slice())?.into()
             }
+            COVER_AUDIT_RESULT => {
+                acproto::CommonNotifySignalCoverAuditResult::decode(item.payload.as_slice())?.into()
+            }
             _ => {
                 log::trace!("unknown notify signal type: {}", item.signal_type);
                 item.payload.into()
@@ -223,3 +364,125 @@ pub(crate) fn notify_signal(payload: &[u8]) -> Result<Vec<NotifySignal>> {
 
     Ok(signals)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pk_state_signal() -> Result<()> {
+        let statistic = acproto::CommonStateSignalPkStatistic {
+            a: "pk".to_string(),
+            j: vec![acproto::PkPlayerStatistic {
+                b: "live".to_string(),
+                c: 100,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let state = acproto::ZtLiveScStateSignal {
+            item: vec![acproto::ZtLiveStateSignalItem {
+                signal_type: PK_STATISTIC.to_string(),
+                payload: statistic.encode_to_vec(),
+            }],
+        };
+        let signals = state_signal(&state.encode_to_vec())?;
+        assert_eq!(signals, vec![StateSignal::PkStatistic(statistic)]);
+        assert_eq!(signals[0].pk_id(), Some("pk"));
+        let players = match &signals[0] {
+            StateSignal::PkStatistic(s) => s.players(),
+            _ => unreachable!(),
+        };
+        assert_eq!(players.len(), 1);
+        assert_eq!(players[0].live_id(), "live");
+        assert_eq!(players[0].score(), 100);
+
+        Ok(())
+    }
+
+    fn decode_state(signal_type: &str, payload: &[u8]) -> Result<Vec<StateSignal>> {
+        let state = acproto::ZtLiveScStateSignal {
+            item: vec![acproto::ZtLiveStateSignalItem {
+                signal_type: signal_type.to_string(),
+                payload: payload.to_vec(),
+            }],
+        };
+        state_signal(&state.encode_to_vec())
+    }
+
+    #[test]
+    fn test_wish_sheet() -> Result<()> {
+        // wire bytes of a wish sheet with one wish
+        let payload = [
+            0x0a, 0x02, b'w', b'1', 0x12, 0x0f, 0x0a, 0x01, b'c', 0x10, 0x02, 0x18, 0x03, 0x20,
+            0x0a, 0x2a, 0x01, b'g', 0x32, 0x01, b'h',
+        ];
+        let expected = acproto::CommonStateSignalWishSheetCurrentState {
+            a: "w1".to_string(),
+            b: vec![
+                acproto::common_state_signal_wish_sheet_current_state::WishCurrentState {
+                    c: "c".to_string(),
+                    d: 2,
+                    e: 3,
+                    f: 10,
+                    g: "g".to_string(),
+                    h: "h".to_string(),
+                },
+            ],
+        };
+        assert_eq!(
+            decode_state(WISH_SHEET, &payload)?,
+            vec![StateSignal::WishSheet(expected)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_feature_state_sync() -> Result<()> {
+        use acproto::live_feature_state::{FeatureState, FeatureType};
+
+        // wire bytes of the landscape comment feature being opened and then closed
+        let payload = [
+            0x0a, 0x04, 0x08, 0x01, 0x10, 0x01, 0x0a, 0x04, 0x08, 0x01, 0x10, 0x02,
+        ];
+        let signals = decode_state(FEATURE_STATE_SYNC, &payload)?;
+        let sync = match signals.as_slice() {
+            [StateSignal::FeatureStateSync(sync)] => sync,
+            _ => panic!("unexpected state signals: {:?}", signals),
+        };
+        let states: Vec<_> = sync
+            .feature_state
+            .iter()
+            .map(|s| (s.r#type(), s.state()))
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                (FeatureType::LandscapeComment, FeatureState::Opend),
+                (FeatureType::LandscapeComment, FeatureState::Closed),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cover_audit_result() -> Result<()> {
+        let audit = acproto::CommonNotifySignalCoverAuditResult {
+            audit_status:
+                acproto::common_notify_signal_cover_audit_result::AuditStatus::CoverAuditFailed
+                    .into(),
+        };
+        let notify = acproto::ZtLiveScNotifySignal {
+            item: vec![acproto::ZtLiveNotifySignalItem {
+                signal_type: COVER_AUDIT_RESULT.to_string(),
+                payload: audit.encode_to_vec(),
+            }],
+        };
+        let signals = notify_signal(&notify.encode_to_vec())?;
+        assert_eq!(signals, vec![NotifySignal::CoverAuditResult(audit)]);
+
+        Ok(())
+    }
+}
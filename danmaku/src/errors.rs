@@ -32,6 +32,14 @@ pub enum Error {
     NoSessionKey,
     #[error("failed to register in the danmaku server")]
     RegisterError,
+    #[error("all danmaku tickets were invalid and there was no token refresher")]
+    TicketsExhausted,
+    #[error("invalid danmaku record")]
+    InvalidRecord,
+    #[error("failed to record the danmaku session: {0}")]
+    RecordError(#[source] std::io::Error),
+    #[error("invalid FLV data")]
+    InvalidFlv,
 
     #[cfg(feature = "api")]
     #[error(transparent)]
@@ -39,6 +47,9 @@ pub enum Error {
     #[cfg(feature = "api")]
     #[error("no live info in api client")]
     NoLiveInfo,
+    #[cfg(feature = "api")]
+    #[error("no stream of the live was selected")]
+    NoStream,
 
     #[cfg(feature = "_serde")]
     #[error(transparent)]
@@ -46,8 +57,20 @@ pub enum Error {
 
     #[cfg(feature = "default_ws_client")]
     #[error(transparent)]
-    TungsteniteError(#[from] async_tungstenite::tungstenite::Error),
+    TungsteniteError(#[from] Box<async_tungstenite::tungstenite::Error>),
+    #[cfg(feature = "default_http_client")]
+    #[error(transparent)]
+    ReqwestError(#[from] reqwest::Error),
+
     #[cfg(feature = "default_ws_client")]
     #[error("it was timeout for the WebSocket client to connect the server")]
     WsConnectTimeout,
 }
+
+#[cfg(feature = "default_ws_client")]
+impl From<async_tungstenite::tungstenite::Error> for Error {
+    #[inline]
+    fn from(e: async_tungstenite::tungstenite::Error) -> Self {
+        Self::TungsteniteError(Box::new(e))
+    }
+}
@@ -0,0 +1,438 @@
+//! Exports comments to Bilibili-style XML danmaku and ASS subtitles.
+
+use crate::{
+    acproto,
+    danmaku::{ActionSignal, Danmaku},
+    Result,
+};
+use std::{collections::HashMap, io::Write, time::Duration};
+
+/// Bright colors which are readable on most videos, in `0xRRGGBB`.
+pub const USER_COLORS: [u32; 12] = [
+    0xFFFFFF, 0xFE0302, 0xFF7204, 0xFFAA02, 0xFFD302, 0xFFFF00, 0xA0EE00, 0x00CD00, 0x019899,
+    0x4266BE, 0x89D5FF, 0xCC0273,
+];
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ExportComment {
+    /// Milliseconds since the start of the live.
+    pub time: i64,
+    pub user_id: i64,
+    pub nickname: String,
+    pub content: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssConfig {
+    pub width: u32,
+    pub height: u32,
+    pub font_name: String,
+    pub font_size: u32,
+    /// The time a comment takes to scroll across the screen.
+    pub duration: Duration,
+    /// The part of the screen from the top used by comments, between 0 and 1.
+    pub area: f64,
+    pub outline: u32,
+    /// Alpha of comments, 0 is opaque and 255 is transparent.
+    pub alpha: u8,
+}
+
+impl Default for AssConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            font_name: "Microsoft YaHei".to_string(),
+            font_size: 48,
+            duration: Duration::from_secs(8),
+            area: 1.0,
+            outline: 2,
+            alpha: 0,
+        }
+    }
+}
+
+impl AssConfig {
+    #[inline]
+    fn lane_height(&self) -> u32 {
+        self.font_size + self.font_size / 8
+    }
+
+    #[inline]
+    fn lanes(&self) -> usize {
+        ((f64::from(self.height) * self.area.clamp(0.0, 1.0)) as u32 / self.lane_height().max(1))
+            .max(1) as usize
+    }
+
+    /// Estimates the width of the text, full-width characters are as wide as the font size.
+    fn text_width(&self, text: &str) -> f64 {
+        text.chars()
+            .map(|c| {
+                if c.is_ascii() {
+                    f64::from(self.font_size) / 2.0
+                } else {
+                    f64::from(self.font_size)
+                }
+            })
+            .sum()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Exporter {
+    start_time: i64,
+    comments: Vec<ExportComment>,
+    user_colors: HashMap<i64, u32>,
+    uniform_color: Option<u32>,
+}
+
+impl Exporter {
+    /// `start_time` is the start time of the live in milliseconds since the Unix epoch, e.g. `Live::start_time`.
+    #[inline]
+    pub fn new(start_time: i64) -> Self {
+        Self {
+            start_time,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn comments(&self) -> &[ExportComment] {
+        &self.comments
+    }
+
+    /// Adds the comment, returns false if it was sent before the live started.
+    pub fn push_comment(&mut self, comment: &acproto::CommonActionSignalComment) -> bool {
+        let time = comment.send_time_ms - self.start_time;
+        if time < 0 {
+            return false;
+        }
+        let user = comment.user_info.clone().unwrap_or_default();
+        self.comments.push(ExportComment {
+            time,
+            user_id: user.user_id,
+            nickname: user.nickname,
+            content: comment.content.clone(),
+        });
+
+        true
+    }
+
+    /// Adds all comments in the danmaku and returns the count of added comments.
+    pub fn push_danmaku(&mut self, danmaku: &Danmaku) -> usize {
+        match danmaku {
+            Danmaku::ActionSignal(signals) => signals
+                .iter()
+                .filter(|s| match s {
+                    ActionSignal::Comment(comment) => self.push_comment(comment),
+                    _ => false,
+                })
+                .count(),
+            _ => 0,
+        }
+    }
+
+    #[inline]
+    pub fn set_user_color(&mut self, user_id: i64, color: u32) -> &mut Self {
+        let _ = self.user_colors.insert(user_id, color & 0xFFFFFF);
+        self
+    }
+
+    /// Uses the same color for all users instead of per-user colors.
+    #[inline]
+    pub fn set_uniform_color(&mut self, color: Option<u32>) -> &mut Self {
+        self.uniform_color = color.map(|c| c & 0xFFFFFF);
+        self
+    }
+
+    /// The color of the user in `0xRRGGBB`.
+    pub fn user_color(&self, user_id: i64) -> u32 {
+        if let Some(color) = self.uniform_color {
+            return color;
+        }
+        match self.user_colors.get(&user_id) {
+            Some(color) => *color,
+            None => {
+                // a cheap integer hash so that adjacent uids get different colors
+                let hash = (user_id as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32;
+                USER_COLORS[(hash % USER_COLORS.len() as u64) as usize]
+            }
+        }
+    }
+
+    #[inline]
+    fn sorted(&self) -> Vec<&ExportComment> {
+        let mut comments: Vec<_> = self.comments.iter().collect();
+        comments.sort_by_key(|c| c.time);
+        comments
+    }
+
+    pub fn write_xml(&self, mut writer: impl Write) -> Result<()> {
+        let comments = self.sorted();
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(writer, "<i>")?;
+        writeln!(writer, "  <chatserver>chat.bilibili.com</chatserver>")?;
+        writeln!(writer, "  <chatid>0</chatid>")?;
+        writeln!(writer, "  <mission>0</mission>")?;
+        writeln!(writer, "  <maxlimit>{}</maxlimit>", comments.len())?;
+        writeln!(writer, "  <state>0</state>")?;
+        writeln!(writer, "  <real_name>0</real_name>")?;
+        writeln!(writer, "  <source>k-v</source>")?;
+        for (i, comment) in comments.into_iter().enumerate() {
+            // time, mode, font size, color, send timestamp, pool, user hash, danmaku ID
+            writeln!(
+                writer,
+                r#"  <d p="{:.3},1,25,{},{},0,{:x},{}">{}</d>"#,
+                comment.time as f64 / 1000.0,
+                self.user_color(comment.user_id),
+                (self.start_time + comment.time) / 1000,
+                comment.user_id,
+                i,
+                escape_xml(&comment.content)
+            )?;
+        }
+        writeln!(writer, "</i>")?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn write_ass(&self, mut writer: impl Write, config: &AssConfig) -> Result<()> {
+        writeln!(writer, "[Script Info]")?;
+        writeln!(writer, "ScriptType: v4.00+")?;
+        writeln!(writer, "PlayResX: {}", config.width)?;
+        writeln!(writer, "PlayResY: {}", config.height)?;
+        writeln!(writer, "WrapStyle: 2")?;
+        writeln!(writer, "ScaledBorderAndShadow: yes")?;
+        writeln!(writer)?;
+        writeln!(writer, "[V4+ Styles]")?;
+        writeln!(writer, "Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding")?;
+        writeln!(
+            writer,
+            "Style: Danmaku,{},{},&H{:02X}FFFFFF,&H{:02X}FFFFFF,&H{:02X}000000,&H{:02X}000000,0,0,0,0,100,100,0,0,1,{},0,7,0,0,0,1",
+            config.font_name,
+            config.font_size,
+            config.alpha,
+            config.alpha,
+            config.alpha,
+            config.alpha,
+            config.outline
+        )?;
+        writeln!(writer)?;
+        writeln!(writer, "[Events]")?;
+        writeln!(
+            writer,
+            "Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text"
+        )?;
+
+        let mut lanes = Lanes::new(config);
+        for comment in self.sorted() {
+            let width = config.text_width(&comment.content);
+            let lane = lanes.allocate(comment.time, width);
+            let y = lane as u32 * config.lane_height();
+            let start = comment.time;
+            let end = start + config.duration.as_millis() as i64;
+            let color = self.user_color(comment.user_id);
+            writeln!(
+                writer,
+                "Dialogue: 0,{},{},Danmaku,{},0,0,0,,{{\\move({},{},{},{})\\c&H{:02X}{:02X}{:02X}&}}{}",
+                ass_time(start),
+                ass_time(end),
+                escape_ass_name(&comment.nickname),
+                config.width,
+                y,
+                -(width.ceil() as i64),
+                y,
+                color & 0xFF,
+                (color >> 8) & 0xFF,
+                (color >> 16) & 0xFF,
+                escape_ass(&comment.content)
+            )?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct LaneState {
+    start: i64,
+    width: f64,
+}
+
+/// Places scrolling comments in lanes so that they neither overlap nor catch up with each other.
+#[derive(Debug)]
+struct Lanes {
+    lanes: Vec<Option<LaneState>>,
+    screen_width: f64,
+    duration: f64,
+}
+
+impl Lanes {
+    #[inline]
+    fn new(config: &AssConfig) -> Self {
+        Self {
+            lanes: vec![None; config.lanes()],
+            screen_width: f64::from(config.width),
+            duration: config.duration.as_millis() as f64,
+        }
+    }
+
+    /// The earliest time a comment with the width can start in the lane.
+    fn free_time(&self, lane: Option<LaneState>, width: f64) -> f64 {
+        match lane {
+            Some(prev) => {
+                let prev_speed = (self.screen_width + prev.width) / self.duration;
+                let speed = (self.screen_width + width) / self.duration;
+                // the tail of the previous comment has entered the screen
+                let entered = prev.start as f64 + prev.width / prev_speed;
+                // the new comment won't reach the left edge before the previous one leaves
+                let left = prev.start as f64 + self.duration - self.screen_width / speed;
+                entered.max(left)
+            }
+            None => f64::MIN,
+        }
+    }
+
+    fn allocate(&mut self, time: i64, width: f64) -> usize {
+        let free: Vec<f64> = self
+            .lanes
+            .iter()
+            .map(|l| self.free_time(*l, width))
+            .collect();
+        let lane = free
+            .iter()
+            .position(|t| *t <= time as f64)
+            .unwrap_or_else(|| {
+                free.iter()
+                    .enumerate()
+                    .min_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
+                    .map(|(i, _)| i)
+                    .unwrap_or_default()
+            });
+        self.lanes[lane] = Some(LaneState { start: time, width });
+
+        lane
+    }
+}
+
+fn ass_time(ms: i64) -> String {
+    let cs = ms.max(0) / 10;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        cs / 360_000,
+        cs / 6000 % 60,
+        cs / 100 % 60,
+        cs % 100
+    )
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn escape_ass(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '{' => escaped.push_str("\\{"),
+            '}' => escaped.push_str("\\}"),
+            '\n' => escaped.push_str("\\N"),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[inline]
+fn escape_ass_name(s: &str) -> String {
+    s.chars().filter(|c| *c != ',' && !c.is_control()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(user_id: i64, time: i64, content: &str) -> acproto::CommonActionSignalComment {
+        acproto::CommonActionSignalComment {
+            content: content.to_string(),
+            send_time_ms: time,
+            user_info: Some(acproto::ZtLiveUserInfo {
+                user_id,
+                nickname: format!("user{}", user_id),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn test_export() -> Result<()> {
+        let mut exporter = Exporter::new(1_000_000);
+        assert!(!exporter.push_comment(&comment(1, 999_000, "before the live")));
+        let danmaku = Danmaku::ActionSignal(vec![
+            ActionSignal::Comment(comment(1, 1_061_500, "a <b> & 'c'")),
+            ActionSignal::Comment(comment(2, 1_061_500, "{弹幕}")),
+        ]);
+        assert_eq!(exporter.push_danmaku(&danmaku), 2);
+        let _ = exporter.set_user_color(2, 0x123456);
+        assert_eq!(exporter.user_color(2), 0x123456);
+        assert_eq!(exporter.user_color(3), exporter.user_color(3));
+
+        let mut xml = Vec::new();
+        exporter.write_xml(&mut xml)?;
+        let xml = String::from_utf8(xml).expect("invalid UTF-8");
+        assert!(xml.contains(&format!(
+            r#"<d p="61.500,1,25,{},1061,0,1,0">a &lt;b&gt; &amp; &apos;c&apos;</d>"#,
+            exporter.user_color(1)
+        )));
+        assert!(xml.contains(r#"<d p="61.500,1,25,1193046,1061,0,2,1">{弹幕}</d>"#));
+
+        let mut ass = Vec::new();
+        exporter.write_ass(&mut ass, &AssConfig::default())?;
+        let ass = String::from_utf8(ass).expect("invalid UTF-8");
+        let dialogues: Vec<_> = ass.lines().filter(|l| l.starts_with("Dialogue:")).collect();
+        assert_eq!(dialogues.len(), 2);
+        assert!(dialogues[0].starts_with("Dialogue: 0,0:01:01.50,0:01:09.50,Danmaku,user1,"));
+        assert!(dialogues[0].contains(r"\move(1920,0,"));
+        // the second comment is sent at the same time, so it's placed in the next lane
+        assert!(dialogues[1].contains(r"\move(1920,54,-144,54)\c&H563412&}\{弹幕\}"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lanes() {
+        let config = AssConfig::default();
+        let mut lanes = Lanes::new(&config);
+        assert_eq!(lanes.lanes.len(), 20);
+        assert_eq!(lanes.allocate(0, 480.0), 0);
+        assert_eq!(lanes.allocate(100, 480.0), 1);
+        // the tail of the first comment has entered the screen
+        assert_eq!(lanes.allocate(2000, 480.0), 0);
+        // a longer comment scrolls faster and would catch up with the previous one
+        assert_eq!(lanes.allocate(2100, 3000.0), 2);
+        for i in 0..20 {
+            let _ = lanes.allocate(3000 + i, 480.0);
+        }
+        assert!(lanes.allocate(3100, 480.0) < 20);
+        assert_eq!(ass_time(3_723_450), "1:02:03.45");
+    }
+}
@@ -0,0 +1,1003 @@
+//! Demuxes FLV streams into tags, parses the `onMetaData` script data and remuxes recordings.
+//!
+//! Only AMF0 is supported in script data.
+
+use crate::{Error, Result};
+use std::{
+    convert::{TryFrom, TryInto},
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::Path,
+    time::Duration,
+};
+
+pub const FLV_SIGNATURE: [u8; 3] = *b"FLV";
+pub const FLV_HEADER_LEN: usize = 9;
+pub const TAG_HEADER_LEN: usize = 11;
+pub const PREV_TAG_SIZE_LEN: usize = 4;
+pub const ON_METADATA: &str = "onMetaData";
+
+const MAX_DATA_SIZE: usize = 0xff_ffff;
+const SOUND_FORMAT_AAC: u8 = 10;
+const CODEC_AVC: u8 = 7;
+const CODEC_HEVC: u8 = 12;
+const FRAME_KEY: u8 = 1;
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+const MAX_AMF_DEPTH: usize = 64;
+/// The default gap in milliseconds, timestamps of a track jumping backward or forward more than
+/// this are discontinuities.
+const MAX_TIMESTAMP_GAP: Duration = Duration::from_secs(1);
+/// The gap in milliseconds between the tags before and after a discontinuity.
+const DISCONTINUITY_GAP: i64 = 40;
+
+const AMF_NUMBER: u8 = 0;
+const AMF_BOOLEAN: u8 = 1;
+const AMF_STRING: u8 = 2;
+const AMF_OBJECT: u8 = 3;
+const AMF_NULL: u8 = 5;
+const AMF_UNDEFINED: u8 = 6;
+const AMF_ECMA_ARRAY: u8 = 8;
+const AMF_OBJECT_END: u8 = 9;
+const AMF_STRICT_ARRAY: u8 = 10;
+const AMF_DATE: u8 = 11;
+const AMF_LONG_STRING: u8 = 12;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TagType {
+    Audio,
+    Video,
+    Script,
+    Unknown(u8),
+}
+
+impl From<u8> for TagType {
+    #[inline]
+    fn from(tag_type: u8) -> Self {
+        match tag_type {
+            8 => Self::Audio,
+            9 => Self::Video,
+            18 => Self::Script,
+            t => Self::Unknown(t),
+        }
+    }
+}
+
+impl From<TagType> for u8 {
+    #[inline]
+    fn from(tag_type: TagType) -> Self {
+        match tag_type {
+            TagType::Audio => 8,
+            TagType::Video => 9,
+            TagType::Script => 18,
+            TagType::Unknown(t) => t,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FlvHeader {
+    pub version: u8,
+    pub has_audio: bool,
+    pub has_video: bool,
+    pub data_offset: u32,
+}
+
+impl Default for FlvHeader {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            version: 1,
+            has_audio: true,
+            has_video: true,
+            data_offset: FLV_HEADER_LEN as u32,
+        }
+    }
+}
+
+impl FlvHeader {
+    /// Parses the header at the start of the data.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < FLV_HEADER_LEN || !data.starts_with(&FLV_SIGNATURE) {
+            return Err(Error::InvalidFlv);
+        }
+        let data_offset = u32::from_be_bytes(data[5..9].try_into()?);
+        if (data_offset as usize) < FLV_HEADER_LEN {
+            return Err(Error::InvalidFlv);
+        }
+
+        Ok(Self {
+            version: data[3],
+            has_audio: data[4] & 0x04 != 0,
+            has_video: data[4] & 0x01 != 0,
+            data_offset,
+        })
+    }
+
+    /// Encodes the header and the first previous tag size.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = FLV_SIGNATURE.to_vec();
+        data.push(self.version);
+        data.push(u8::from(self.has_audio) << 2 | u8::from(self.has_video));
+        data.extend_from_slice(&self.data_offset.to_be_bytes());
+        data.resize(self.data_offset as usize + PREV_TAG_SIZE_LEN, 0);
+        data
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Tag {
+    pub tag_type: TagType,
+    /// Whether the data is encrypted.
+    pub filtered: bool,
+    /// In milliseconds.
+    pub timestamp: u32,
+    pub stream_id: u32,
+    pub data: Vec<u8>,
+}
+
+impl Tag {
+    #[inline]
+    pub fn new(tag_type: TagType, timestamp: u32, data: Vec<u8>) -> Self {
+        Self {
+            tag_type,
+            filtered: false,
+            timestamp,
+            stream_id: 0,
+            data,
+        }
+    }
+
+    /// The size of the tag with its header and the previous tag size after it.
+    #[inline]
+    pub fn size(&self) -> usize {
+        TAG_HEADER_LEN + self.data.len() + PREV_TAG_SIZE_LEN
+    }
+
+    #[inline]
+    pub fn is_media(&self) -> bool {
+        matches!(self.tag_type, TagType::Audio | TagType::Video)
+    }
+
+    /// Whether it's an AAC sequence header or an AVC/HEVC sequence header.
+    pub fn is_sequence_header(&self) -> bool {
+        let data = &self.data;
+        match self.tag_type {
+            TagType::Audio => data.len() >= 2 && data[0] >> 4 == SOUND_FORMAT_AAC && data[1] == 0,
+            TagType::Video => {
+                data.len() >= 2 && matches!(data[0] & 0x0f, CODEC_AVC | CODEC_HEVC) && data[1] == 0
+            }
+            _ => false,
+        }
+    }
+
+    #[inline]
+    pub fn is_keyframe(&self) -> bool {
+        self.tag_type == TagType::Video
+            && matches!(self.data.first(), Some(d) if d >> 4 == FRAME_KEY)
+    }
+
+    #[inline]
+    pub fn script_data(&self) -> Result<ScriptData> {
+        if self.tag_type == TagType::Script {
+            ScriptData::parse(&self.data)
+        } else {
+            Err(Error::InvalidFlv)
+        }
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<()> {
+        if self.data.len() > MAX_DATA_SIZE {
+            return Err(Error::InvalidFlv);
+        }
+        let mut header = [0; TAG_HEADER_LEN];
+        header[0] = u8::from(self.filtered) << 5 | u8::from(self.tag_type);
+        header[1..4].copy_from_slice(&(self.data.len() as u32).to_be_bytes()[1..]);
+        let [extended, high, middle, low] = self.timestamp.to_be_bytes();
+        header[4..8].copy_from_slice(&[high, middle, low, extended]);
+        header[8..].copy_from_slice(&self.stream_id.to_be_bytes()[1..]);
+        writer.write_all(&header)?;
+        writer.write_all(&self.data)?;
+        writer.write_all(&((TAG_HEADER_LEN + self.data.len()) as u32).to_be_bytes())?;
+
+        Ok(())
+    }
+
+    /// Fails if the data is larger than the max size of a tag, 16 MiB - 1.
+    #[inline]
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(self.size());
+        self.write_to(&mut data)?;
+        Ok(data)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FlvItem {
+    /// A stream may contain more headers, e.g. streams concatenated after reconnecting.
+    Header(FlvHeader),
+    Tag(Tag),
+}
+
+/// Cuts the data pushed into it into the FLV header and tags.
+#[derive(Clone, Debug, Default)]
+pub struct Demuxer {
+    buf: Vec<u8>,
+    header_read: bool,
+}
+
+impl Demuxer {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// The data which hasn't formed a complete header or tag yet.
+    #[inline]
+    pub fn buffered(&self) -> &[u8] {
+        &self.buf
+    }
+
+    fn next_header(&mut self) -> Result<Option<FlvHeader>> {
+        if self.buf.len() < FLV_HEADER_LEN {
+            return Ok(None);
+        }
+        let header = FlvHeader::parse(&self.buf)?;
+        let len = header.data_offset as usize + PREV_TAG_SIZE_LEN;
+        if self.buf.len() < len {
+            return Ok(None);
+        }
+        let _ = self.buf.drain(..len);
+        self.header_read = true;
+
+        Ok(Some(header))
+    }
+
+    /// Returns the next header or tag, `None` if more data is needed.
+    pub fn next_item(&mut self) -> Result<Option<FlvItem>> {
+        // the type of a tag is never `F`, so it's another stream
+        if !self.header_read || self.buf.starts_with(&FLV_SIGNATURE) {
+            return Ok(self.next_header()?.map(FlvItem::Header));
+        }
+        if self.buf.len() < TAG_HEADER_LEN {
+            return Ok(None);
+        }
+        let header = &self.buf[..TAG_HEADER_LEN];
+        let data_size = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let len = TAG_HEADER_LEN + data_size + PREV_TAG_SIZE_LEN;
+        if self.buf.len() < len {
+            return Ok(None);
+        }
+        let tag = Tag {
+            tag_type: TagType::from(header[0] & 0x1f),
+            filtered: header[0] & 0x20 != 0,
+            timestamp: u32::from_be_bytes([header[7], header[4], header[5], header[6]]),
+            stream_id: u32::from_be_bytes([0, header[8], header[9], header[10]]),
+            data: self.buf[TAG_HEADER_LEN..TAG_HEADER_LEN + data_size].to_vec(),
+        };
+        let _ = self.buf.drain(..len);
+
+        Ok(Some(FlvItem::Tag(tag)))
+    }
+}
+
+/// Iterates the headers and tags in an FLV file.
+///
+/// The iteration ends at the end of the file or after an error. A file cut in the middle of a tag,
+/// e.g. a recording which was interrupted, ends at the last complete tag, see `is_truncated()`.
+#[derive(Debug)]
+pub struct FlvReader<R> {
+    reader: R,
+    demuxer: Demuxer,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> FlvReader<R> {
+    #[inline]
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            demuxer: Demuxer::new(),
+            buf: vec![0; READ_BUFFER_SIZE],
+            done: false,
+        }
+    }
+
+    /// Whether the file ended with an incomplete header or tag.
+    #[inline]
+    pub fn is_truncated(&self) -> bool {
+        self.done && !self.demuxer.buffered().is_empty()
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for FlvReader<R> {
+    type Item = Result<FlvItem>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.demuxer.next_item() {
+                Ok(Some(item)) => return Some(Ok(item)),
+                Ok(None) => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+            match self.reader.read(&mut self.buf) {
+                Ok(0) => self.done = true,
+                Ok(n) => self.demuxer.push(&self.buf[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// An AMF0 value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AmfValue {
+    Number(f64),
+    Boolean(bool),
+    String(String),
+    Object(Vec<(String, AmfValue)>),
+    Null,
+    Undefined,
+    EcmaArray(Vec<(String, AmfValue)>),
+    StrictArray(Vec<AmfValue>),
+    Date { millis: f64, time_zone: i16 },
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if data.len() < len {
+        return Err(Error::InvalidFlv);
+    }
+    let (taken, rest) = data.split_at(len);
+    *data = rest;
+    Ok(taken)
+}
+
+#[inline]
+fn take_u16(data: &mut &[u8]) -> Result<u16> {
+    Ok(u16::from_be_bytes(take(data, 2)?.try_into()?))
+}
+
+#[inline]
+fn take_u32(data: &mut &[u8]) -> Result<u32> {
+    Ok(u32::from_be_bytes(take(data, 4)?.try_into()?))
+}
+
+#[inline]
+fn take_f64(data: &mut &[u8]) -> Result<f64> {
+    Ok(f64::from_be_bytes(take(data, 8)?.try_into()?))
+}
+
+fn take_string(data: &mut &[u8], len: usize) -> Result<String> {
+    String::from_utf8(take(data, len)?.to_vec()).map_err(|_| Error::InvalidFlv)
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &str) {
+    // keys longer than u16::MAX can't be encoded, so they are cut
+    let s = &s.as_bytes()[..s.len().min(u16::MAX as usize)];
+    buf.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    buf.extend_from_slice(s);
+}
+
+impl AmfValue {
+    /// Decodes the value at the start of the data and advances the data past it.
+    #[inline]
+    pub fn decode(data: &mut &[u8]) -> Result<Self> {
+        Self::decode_value(data, 0)
+    }
+
+    fn decode_properties(data: &mut &[u8], depth: usize) -> Result<Vec<(String, AmfValue)>> {
+        let mut properties = Vec::new();
+        // some encoders omit the object end marker at the end of the data
+        while !data.is_empty() {
+            let len = take_u16(data)? as usize;
+            if len == 0 && data.first() == Some(&AMF_OBJECT_END) {
+                *data = &data[1..];
+                break;
+            }
+            let key = take_string(data, len)?;
+            properties.push((key, Self::decode_value(data, depth + 1)?));
+        }
+
+        Ok(properties)
+    }
+
+    fn decode_value(data: &mut &[u8], depth: usize) -> Result<Self> {
+        if depth > MAX_AMF_DEPTH {
+            return Err(Error::InvalidFlv);
+        }
+        let value = match take(data, 1)?[0] {
+            AMF_NUMBER => Self::Number(take_f64(data)?),
+            AMF_BOOLEAN => Self::Boolean(take(data, 1)?[0] != 0),
+            AMF_STRING => {
+                let len = take_u16(data)? as usize;
+                Self::String(take_string(data, len)?)
+            }
+            AMF_LONG_STRING => {
+                let len = take_u32(data)? as usize;
+                Self::String(take_string(data, len)?)
+            }
+            AMF_OBJECT => Self::Object(Self::decode_properties(data, depth)?),
+            AMF_NULL => Self::Null,
+            AMF_UNDEFINED => Self::Undefined,
+            AMF_ECMA_ARRAY => {
+                // the count is only a hint
+                let _ = take_u32(data)?;
+                Self::EcmaArray(Self::decode_properties(data, depth)?)
+            }
+            AMF_STRICT_ARRAY => {
+                let count = take_u32(data)?;
+                let values = (0..count)
+                    .map(|_| Self::decode_value(data, depth + 1))
+                    .collect::<Result<_>>()?;
+                Self::StrictArray(values)
+            }
+            AMF_DATE => Self::Date {
+                millis: take_f64(data)?,
+                time_zone: take_u16(data)? as i16,
+            },
+            _ => return Err(Error::InvalidFlv),
+        };
+
+        Ok(value)
+    }
+
+    fn encode_properties(buf: &mut Vec<u8>, properties: &[(String, AmfValue)]) {
+        for (key, value) in properties {
+            put_string(buf, key);
+            value.encode(buf);
+        }
+        buf.extend_from_slice(&[0, 0, AMF_OBJECT_END]);
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Number(n) => {
+                buf.push(AMF_NUMBER);
+                buf.extend_from_slice(&n.to_be_bytes());
+            }
+            Self::Boolean(b) => buf.extend_from_slice(&[AMF_BOOLEAN, u8::from(*b)]),
+            Self::String(s) if s.len() > u16::MAX as usize => {
+                buf.push(AMF_LONG_STRING);
+                buf.extend_from_slice(&(s.len() as u32).to_be_bytes());
+                buf.extend_from_slice(s.as_bytes());
+            }
+            Self::String(s) => {
+                buf.push(AMF_STRING);
+                put_string(buf, s);
+            }
+            Self::Object(properties) => {
+                buf.push(AMF_OBJECT);
+                Self::encode_properties(buf, properties);
+            }
+            Self::Null => buf.push(AMF_NULL),
+            Self::Undefined => buf.push(AMF_UNDEFINED),
+            Self::EcmaArray(properties) => {
+                buf.push(AMF_ECMA_ARRAY);
+                buf.extend_from_slice(&(properties.len() as u32).to_be_bytes());
+                Self::encode_properties(buf, properties);
+            }
+            Self::StrictArray(values) => {
+                buf.push(AMF_STRICT_ARRAY);
+                buf.extend_from_slice(&(values.len() as u32).to_be_bytes());
+                for value in values {
+                    value.encode(buf);
+                }
+            }
+            Self::Date { millis, time_zone } => {
+                buf.push(AMF_DATE);
+                buf.extend_from_slice(&millis.to_be_bytes());
+                buf.extend_from_slice(&time_zone.to_be_bytes());
+            }
+        }
+    }
+
+    #[inline]
+    fn properties(&self) -> Option<&Vec<(String, AmfValue)>> {
+        match self {
+            Self::Object(properties) | Self::EcmaArray(properties) => Some(properties),
+            _ => None,
+        }
+    }
+
+    /// Gets the property of an object or an ECMA array.
+    #[inline]
+    pub fn get(&self, key: &str) -> Option<&AmfValue> {
+        self.properties()?
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Sets the property of an object or an ECMA array, appends it if there isn't such a property.
+    /// Other values are replaced with an ECMA array.
+    pub fn set(&mut self, key: &str, value: AmfValue) {
+        if self.properties().is_none() {
+            *self = Self::EcmaArray(Vec::new());
+        }
+        if let Self::Object(properties) | Self::EcmaArray(properties) = self {
+            match properties.iter_mut().find(|(k, _)| k == key) {
+                Some((_, v)) => *v = value,
+                None => properties.push((key.to_string(), value)),
+            }
+        }
+    }
+
+    #[inline]
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// The data of a script tag, i.e. a name and a value, e.g. `onMetaData` and its properties.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptData {
+    pub name: String,
+    pub value: AmfValue,
+}
+
+impl ScriptData {
+    #[inline]
+    pub fn metadata(value: AmfValue) -> Self {
+        Self {
+            name: ON_METADATA.to_string(),
+            value,
+        }
+    }
+
+    pub fn parse(mut data: &[u8]) -> Result<Self> {
+        let name = match AmfValue::decode(&mut data)? {
+            AmfValue::String(name) => name,
+            _ => return Err(Error::InvalidFlv),
+        };
+        let value = if data.is_empty() {
+            AmfValue::Undefined
+        } else {
+            AmfValue::decode(&mut data)?
+        };
+
+        Ok(Self { name, value })
+    }
+
+    #[inline]
+    pub fn is_metadata(&self) -> bool {
+        self.name == ON_METADATA
+    }
+
+    /// The duration in seconds in the metadata.
+    #[inline]
+    pub fn duration(&self) -> Option<f64> {
+        self.value.get("duration")?.as_f64()
+    }
+
+    /// The file size in bytes in the metadata.
+    #[inline]
+    pub fn file_size(&self) -> Option<f64> {
+        self.value.get("filesize")?.as_f64()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        AmfValue::String(self.name.clone()).encode(&mut data);
+        self.value.encode(&mut data);
+        data
+    }
+
+    #[inline]
+    pub fn to_tag(&self) -> Tag {
+        Tag::new(TagType::Script, 0, self.to_bytes())
+    }
+}
+
+/// Makes the timestamps of media tags start from zero and removes the jumps in them, e.g. after
+/// the stream was reconnected.
+///
+/// Audio and video are checked against their own last timestamps, so an offset between them
+/// isn't a jump. A pause longer than the max gap is taken as a jump.
+#[derive(Clone, Debug)]
+pub struct TimestampFixer {
+    offset: i64,
+    /// The last timestamps of audio and video.
+    last: [Option<i64>; 2],
+    max_gap: i64,
+    discontinuities: u32,
+}
+
+impl Default for TimestampFixer {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            last: [None; 2],
+            max_gap: MAX_TIMESTAMP_GAP.as_millis() as i64,
+            discontinuities: 0,
+        }
+    }
+}
+
+impl TimestampFixer {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the max gap between the timestamps of a track, the default is 1 second.
+    #[inline]
+    pub fn max_gap(mut self, gap: Duration) -> Self {
+        self.max_gap = i64::try_from(gap.as_millis()).unwrap_or(i64::MAX);
+        self
+    }
+
+    #[inline]
+    fn last(&self) -> Option<i64> {
+        self.last.iter().flatten().max().copied()
+    }
+
+    /// Fixes the timestamp of the media tag in place, other tags get timestamp 0.
+    ///
+    /// Sequence headers get the last timestamp as they often restart from 0 after reconnecting.
+    pub fn fix(&mut self, tag: &mut Tag) {
+        if !tag.is_media() {
+            tag.timestamp = 0;
+            return;
+        }
+        if tag.is_sequence_header() {
+            tag.timestamp = self.last_timestamp();
+            return;
+        }
+        let track = usize::from(tag.tag_type == TagType::Video);
+        let mut timestamp = i64::from(tag.timestamp) + self.offset;
+        match (self.last(), self.last[track]) {
+            (None, _) => {
+                self.offset = -timestamp;
+                timestamp = 0;
+            }
+            (_, Some(last))
+                if timestamp.saturating_add(self.max_gap) < last
+                    || timestamp > last.saturating_add(self.max_gap) =>
+            {
+                self.offset += last + DISCONTINUITY_GAP - timestamp;
+                timestamp = last + DISCONTINUITY_GAP;
+                self.discontinuities += 1;
+            }
+            _ => {}
+        }
+        let timestamp = timestamp.clamp(0, i64::from(u32::MAX));
+        let last = &mut self.last[track];
+        *last = Some(last.map_or(timestamp, |last| last.max(timestamp)));
+        tag.timestamp = timestamp as u32;
+    }
+
+    /// The largest fixed timestamp in milliseconds.
+    #[inline]
+    pub fn last_timestamp(&self) -> u32 {
+        self.last().unwrap_or_default() as u32
+    }
+
+    #[inline]
+    pub fn discontinuities(&self) -> u32 {
+        self.discontinuities
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RemuxSummary {
+    /// In milliseconds.
+    pub duration: u32,
+    pub file_size: u64,
+    pub media_tags: usize,
+    pub discontinuities: u32,
+    /// Whether the input ended with an incomplete tag, which was dropped.
+    pub truncated: bool,
+}
+
+/// Writes the header, the metadata with placeholders and the tags before the first media tag,
+/// returns the position of the metadata.
+fn start_remux<W: Write + Seek>(
+    writer: &mut W,
+    header: Option<FlvHeader>,
+    metadata: &mut ScriptData,
+    pending: Vec<Tag>,
+    fixer: &mut TimestampFixer,
+) -> Result<u64> {
+    writer.write_all(&header.unwrap_or_default().to_bytes())?;
+    metadata.value.set("duration", AmfValue::Number(0.0));
+    metadata.value.set("filesize", AmfValue::Number(0.0));
+    let pos = writer.stream_position()?;
+    metadata.to_tag().write_to(writer)?;
+    for mut tag in pending {
+        fixer.fix(&mut tag);
+        tag.write_to(writer)?;
+    }
+
+    Ok(pos)
+}
+
+/// Copies the FLV to the writer with fixed timestamps and the `duration` and `filesize` in
+/// `onMetaData` rewritten, so the output is seekable by players.
+///
+/// Headers after the first one and `onMetaData` after the first media tag are dropped, e.g. when
+/// the input is recordings concatenated after reconnecting. `onMetaData` is added if there isn't.
+#[inline]
+pub fn remux<R, W>(reader: R, writer: W) -> Result<RemuxSummary>
+where
+    R: Read,
+    W: Write + Seek,
+{
+    remux_with(reader, writer, TimestampFixer::new())
+}
+
+/// Same as `remux()` but fixes the timestamps with the fixer, e.g. one with a larger max gap for
+/// streams which pause.
+pub fn remux_with<R, W>(reader: R, mut writer: W, mut fixer: TimestampFixer) -> Result<RemuxSummary>
+where
+    R: Read,
+    W: Write + Seek,
+{
+    let mut reader = FlvReader::new(reader);
+    let mut header = None;
+    let mut metadata = None;
+    let mut pending = Vec::new();
+    let start = writer.stream_position()?;
+    let mut metadata_pos = None;
+    let mut summary = RemuxSummary::default();
+    for item in &mut reader {
+        let mut tag = match item? {
+            FlvItem::Header(h) => {
+                let _ = header.get_or_insert(h);
+                continue;
+            }
+            FlvItem::Tag(tag) => tag,
+        };
+        if tag.tag_type == TagType::Script {
+            match tag.script_data() {
+                Ok(data) if data.is_metadata() => {
+                    if metadata_pos.is_none() && metadata.is_none() {
+                        metadata = Some(data);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        if metadata_pos.is_none() {
+            if !tag.is_media() {
+                pending.push(tag);
+                continue;
+            }
+            let metadata = metadata.get_or_insert_with(|| ScriptData::metadata(AmfValue::Null));
+            let pending = std::mem::take(&mut pending);
+            metadata_pos = Some(start_remux(
+                &mut writer,
+                header,
+                metadata,
+                pending,
+                &mut fixer,
+            )?);
+        }
+        fixer.fix(&mut tag);
+        tag.write_to(&mut writer)?;
+        if tag.is_media() {
+            summary.media_tags += 1;
+        }
+    }
+    summary.truncated = reader.is_truncated();
+    let mut metadata = metadata.unwrap_or_else(|| ScriptData::metadata(AmfValue::Null));
+    let metadata_pos = match metadata_pos {
+        Some(pos) => pos,
+        // no media tag
+        None => start_remux(&mut writer, header, &mut metadata, pending, &mut fixer)?,
+    };
+    let end = writer.stream_position()?;
+    summary.duration = fixer.last_timestamp();
+    summary.file_size = end - start;
+    summary.discontinuities = fixer.discontinuities();
+    // numbers have a fixed size, so the metadata tag keeps its size
+    metadata.value.set(
+        "duration",
+        AmfValue::Number(f64::from(summary.duration) / 1000.0),
+    );
+    metadata
+        .value
+        .set("filesize", AmfValue::Number(summary.file_size as f64));
+    let _ = writer.seek(SeekFrom::Start(metadata_pos))?;
+    metadata.to_tag().write_to(&mut writer)?;
+    let _ = writer.seek(SeekFrom::Start(end))?;
+    writer.flush()?;
+
+    Ok(summary)
+}
+
+/// Remuxes the FLV file to the output file, see `remux()`.
+pub fn remux_file(input: impl AsRef<Path>, output: impl AsRef<Path>) -> Result<RemuxSummary> {
+    let reader = BufReader::new(File::open(input)?);
+    let writer = BufWriter::new(File::create(output)?);
+    remux(reader, writer)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// An FLV with a keyframe every second for 5 seconds, starting at the timestamp.
+    pub(crate) fn sample_flv(start: u32) -> Vec<u8> {
+        let mut flv = FlvHeader::default().to_bytes();
+        let metadata = ScriptData::metadata(AmfValue::EcmaArray(vec![
+            ("duration".to_string(), AmfValue::Number(0.0)),
+            ("width".to_string(), AmfValue::Number(1280.0)),
+            ("encoder".to_string(), AmfValue::String("mock".to_string())),
+        ]));
+        let mut tags = vec![
+            metadata.to_tag(),
+            Tag::new(TagType::Video, 0, vec![0x17, 0, 0, 0, 0]),
+            Tag::new(TagType::Audio, 0, vec![0xaf, 0, 0x12, 0x10]),
+        ];
+        for second in 0..5 {
+            let timestamp = start + second * 1000;
+            tags.push(Tag::new(TagType::Video, timestamp, vec![0x17, 1, 0, 0, 0]));
+            tags.push(Tag::new(TagType::Audio, timestamp + 20, vec![0xaf, 1, 0]));
+            tags.push(Tag::new(
+                TagType::Video,
+                timestamp + 500,
+                vec![0x27, 1, 0, 0, 0],
+            ));
+        }
+        for tag in tags {
+            flv.extend(tag.to_bytes().expect("the sample tag is too large"));
+        }
+        flv
+    }
+
+    fn read(data: &[u8]) -> Result<Vec<FlvItem>> {
+        FlvReader::new(data).collect()
+    }
+
+    #[test]
+    fn test_flv() -> Result<()> {
+        let sample = sample_flv(10_000);
+        let items = read(&sample)?;
+        assert_eq!(items.len(), 19);
+        assert_eq!(items[0], FlvItem::Header(FlvHeader::default()));
+        let tags: Vec<_> = items[1..]
+            .iter()
+            .filter_map(|i| match i {
+                FlvItem::Tag(tag) => Some(tag),
+                FlvItem::Header(_) => None,
+            })
+            .collect();
+        let metadata = tags[0].script_data()?;
+        assert!(metadata.is_metadata());
+        assert_eq!(metadata.value.get("width"), Some(&AmfValue::Number(1280.0)));
+        assert_eq!(
+            metadata.value.get("encoder").and_then(AmfValue::as_str),
+            Some("mock")
+        );
+        assert!(tags[1].is_sequence_header() && tags[2].is_sequence_header());
+        assert!(tags[3].is_keyframe() && !tags[5].is_keyframe());
+        assert_eq!(tags[4].timestamp, 10_020);
+        assert_eq!(ScriptData::parse(&metadata.to_bytes())?, metadata);
+
+        // concatenated after reconnecting and cut in the middle of a tag
+        let mut data = sample.clone();
+        data.extend(sample_flv(50_000));
+        data.truncate(data.len() - 3);
+        let mut reader = FlvReader::new(data.as_slice());
+        let items = reader.by_ref().collect::<Result<Vec<_>>>()?;
+        assert_eq!(items.len(), 37);
+        assert!(matches!(items[19], FlvItem::Header(_)));
+        assert!(reader.is_truncated());
+
+        let mut output = Cursor::new(Vec::new());
+        let summary = remux(data.as_slice(), &mut output)?;
+        assert_eq!(
+            summary,
+            RemuxSummary {
+                duration: 8560,
+                file_size: output.get_ref().len() as u64,
+                media_tags: 33,
+                discontinuities: 1,
+                truncated: true,
+            }
+        );
+        let items = read(output.get_ref())?;
+        assert_eq!(items.len(), 1 + 1 + 33);
+        let tags: Vec<_> = items
+            .iter()
+            .filter_map(|i| match i {
+                FlvItem::Tag(tag) => Some(tag),
+                FlvItem::Header(_) => None,
+            })
+            .collect();
+        let metadata = tags[0].script_data()?;
+        assert_eq!(metadata.duration(), Some(8.56));
+        assert_eq!(metadata.file_size(), Some(summary.file_size as f64));
+        assert_eq!(
+            metadata.value.get("encoder").and_then(AmfValue::as_str),
+            Some("mock")
+        );
+        let timestamps: Vec<_> = tags
+            .iter()
+            .filter(|t| t.is_media() && !t.is_sequence_header())
+            .map(|t| t.timestamp)
+            .collect();
+        assert_eq!(&timestamps[..4], &[0, 20, 500, 1000]);
+        // the second stream continues after the first one
+        assert_eq!(&timestamps[15..18], &[4540, 4560, 5040]);
+        assert!(timestamps.windows(2).all(|w| w[0] <= w[1] + 500));
+
+        Ok(())
+    }
+    #[test]
+    fn test_timestamp_fixer() -> Result<()> {
+        let fix = |fixer: &mut TimestampFixer, tags: &[(TagType, u32)]| -> Vec<u32> {
+            tags.iter()
+                .map(|&(tag_type, timestamp)| {
+                    let mut tag = Tag::new(tag_type, timestamp, vec![0x27, 1]);
+                    fixer.fix(&mut tag);
+                    tag.timestamp
+                })
+                .collect()
+        };
+
+        // audio is 1.5s ahead of video
+        let mut fixer = TimestampFixer::new();
+        let tags = [
+            (TagType::Video, 1000),
+            (TagType::Audio, 2500),
+            (TagType::Video, 1040),
+            (TagType::Audio, 2520),
+            (TagType::Video, 1080),
+        ];
+        assert_eq!(fix(&mut fixer, &tags), vec![0, 1500, 40, 1520, 80]);
+        assert_eq!(fixer.discontinuities(), 0);
+        assert_eq!(fixer.last_timestamp(), 1520);
+
+        // the stream paused for 5s
+        let tags = [
+            (TagType::Video, 0),
+            (TagType::Video, 40),
+            (TagType::Video, 5040),
+        ];
+        let mut fixer = TimestampFixer::new();
+        assert_eq!(fix(&mut fixer, &tags), vec![0, 40, 80]);
+        assert_eq!(fixer.discontinuities(), 1);
+        let mut fixer = TimestampFixer::new().max_gap(Duration::from_secs(10));
+        assert_eq!(fix(&mut fixer, &tags), vec![0, 40, 5040]);
+        assert_eq!(fixer.discontinuities(), 0);
+
+        let tag = Tag::new(TagType::Video, 0, vec![0; MAX_DATA_SIZE + 1]);
+        assert!(matches!(tag.to_bytes(), Err(Error::InvalidFlv)));
+
+        Ok(())
+    }
+}
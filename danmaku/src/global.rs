@@ -49,8 +49,17 @@ pub(crate) const AUTHOR_CHAT_ACCEPT: &str = "CommonStateSignalAuthorChatAccept";
 pub(crate) const AUTHOR_CHAT_READY: &str = "CommonStateSignalAuthorChatReady";
 pub(crate) const AUTHOR_CHAT_END: &str = "CommonStateSignalAuthorChatEnd";
 pub(crate) const SOUND_CONFIG: &str = "CommonStateSignalAuthorChatChangeSoundConfig";
+pub(crate) const PK_INVITATION: &str = "CommonStateSignalPKInvitation";
+pub(crate) const PK_ACCEPT: &str = "CommonStateSignalPKAccept";
+pub(crate) const PK_READY: &str = "CommonStateSignalPKReady";
+pub(crate) const PK_SOUND_CONFIG: &str = "CommonStateSignalPKSoundConfigChanged";
+pub(crate) const PK_END: &str = "CommonStateSignalPkEnd";
+pub(crate) const PK_STATISTIC: &str = "CommonStateSignalPkStatistic";
+pub(crate) const WISH_SHEET: &str = "CommonStateSignalWishSheetCurrentState";
+pub(crate) const FEATURE_STATE_SYNC: &str = "CommonStateSignalFeatureStateSync";
 //pub(crate) const LIVE_STATE: &str = "CommonStateSignalLiveState";
 
 pub(crate) const KICKED_OUT: &str = "CommonNotifySignalKickedOut";
 pub(crate) const VIOLATION_ALERT: &str = "CommonNotifySignalViolationAlert";
 pub(crate) const MANAGER_STATE: &str = "CommonNotifySignalLiveManagerState";
+pub(crate) const COVER_AUDIT_RESULT: &str = "CommonNotifySignalCoverAuditResult";
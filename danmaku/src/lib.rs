@@ -2,8 +2,21 @@
 
 pub mod client;
 pub mod danmaku;
+pub mod export;
+pub mod flv;
+pub mod pool;
+pub mod record;
+pub mod server;
 pub mod websocket;
 
+#[cfg(any(feature = "mock", all(test, feature = "default_ws_client")))]
+pub mod mock;
+
+#[cfg(feature = "api")]
+pub mod stream_record;
+#[cfg(feature = "api")]
+pub mod watch;
+
 mod errors;
 mod global;
 mod proto;
@@ -0,0 +1,210 @@
+//! A local WebSocket server speaking the danmaku protocol, for testing without AcFun's servers.
+
+use crate::{
+    acproto,
+    client::DanmakuToken,
+    server::{ClientCommand, DanmakuServerProto, ServerMessage},
+    Result,
+};
+use asynchronous_codec::Framed;
+use futures::{SinkExt, StreamExt};
+use std::{
+    convert::TryFrom,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+use ws_stream_tungstenite::WsStream;
+
+pub const MOCK_SECURITY_KEY: &str = "giEyDh9ECKoxyj6kID4eXg==";
+
+#[derive(Debug)]
+struct Config {
+    token: DanmakuToken,
+    messages: Vec<ServerMessage>,
+    heartbeat_interval: Duration,
+    drop_connections: usize,
+    connections: AtomicUsize,
+}
+
+/// A danmaku server listening on `127.0.0.1` which accepts the token returned by `token()`.
+///
+/// Every connection is registered, entered into the room and then gets the pushed messages in order.
+#[derive(Debug)]
+pub struct MockDanmakuServer {
+    listener: TcpListener,
+    addr: SocketAddr,
+    config: Config,
+}
+
+impl MockDanmakuServer {
+    pub async fn bind() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+
+        Ok(Self {
+            listener,
+            addr,
+            config: Config {
+                token: DanmakuToken {
+                    user_id: 1_000_000_000,
+                    liver_uid: 1,
+                    security_key: MOCK_SECURITY_KEY.to_string(),
+                    service_token: "mock_service_token".to_string(),
+                    live_id: "mock_live_id".to_string(),
+                    enter_room_attach: "mock_enter_room_attach".to_string(),
+                    tickets: vec!["mock_ticket_1".to_string(), "mock_ticket_2".to_string()],
+                },
+                messages: Vec::new(),
+                heartbeat_interval: Duration::from_secs(10),
+                drop_connections: 0,
+                connections: AtomicUsize::new(0),
+            },
+        })
+    }
+
+    #[inline]
+    pub fn url(&self) -> String {
+        format!("ws://{}/", self.addr)
+    }
+
+    #[inline]
+    pub fn token(&self) -> DanmakuToken {
+        self.config.token.clone()
+    }
+
+    /// Replaces the token accepted by the server, the security key must be valid base64.
+    #[inline]
+    pub fn with_token(mut self, token: DanmakuToken) -> Self {
+        self.config.token = token;
+        self
+    }
+
+    #[inline]
+    pub fn heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.config.heartbeat_interval = interval;
+        self
+    }
+
+    /// Closes the first `count` connections right after they enter the room.
+    #[inline]
+    pub fn drop_connections(mut self, count: usize) -> Self {
+        self.config.drop_connections = count;
+        self
+    }
+
+    #[inline]
+    pub fn push_message(mut self, message: ServerMessage) -> Self {
+        self.config.messages.push(message);
+        self
+    }
+
+    #[inline]
+    pub fn push_action_signal(self, signal_type: impl Into<String>, payload: Vec<u8>) -> Self {
+        self.push_message(ServerMessage::ActionSignal(acproto::ZtLiveScActionSignal {
+            item: vec![acproto::ZtLiveActionSignalItem {
+                signal_type: signal_type.into(),
+                payload: vec![payload],
+            }],
+        }))
+    }
+
+    #[inline]
+    pub fn push_state_signal(self, signal_type: impl Into<String>, payload: Vec<u8>) -> Self {
+        self.push_message(ServerMessage::StateSignal(acproto::ZtLiveScStateSignal {
+            item: vec![acproto::ZtLiveStateSignalItem {
+                signal_type: signal_type.into(),
+                payload,
+            }],
+        }))
+    }
+
+    #[inline]
+    pub fn push_status_changed(self, status: acproto::ZtLiveScStatusChanged) -> Self {
+        self.push_message(ServerMessage::StatusChanged(status))
+    }
+
+    /// Serves connections in the background until the returned task is aborted.
+    pub fn spawn(self) -> JoinHandle<()> {
+        let listener = self.listener;
+        let config = Arc::new(self.config);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let config = config.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve(stream, &config).await {
+                        log::trace!("mock danmaku server error: {}", e);
+                    }
+                });
+            }
+        })
+    }
+}
+
+async fn serve(stream: TcpStream, config: &Config) -> Result<()> {
+    let index = config.connections.fetch_add(1, Ordering::SeqCst);
+    let ws = WsStream::new(async_tungstenite::tokio::accept_async(stream).await?);
+    let mut framed = Framed::new(ws, DanmakuServerProto::new(&config.token.security_key)?);
+
+    while let Some(result) = framed.next().await {
+        match result?.command {
+            ClientCommand::Register { service_token, .. } => {
+                if service_token != config.token.service_token {
+                    log::trace!("mock danmaku server got an invalid service token");
+                    break;
+                }
+                framed.send(ServerMessage::register_response()).await?;
+            }
+            ClientCommand::Unregister => {
+                framed.send(ServerMessage::Unregister).await?;
+                break;
+            }
+            ClientCommand::KeepAlive(_) => framed.send(ServerMessage::KeepAlive).await?,
+            ClientCommand::EnterRoom {
+                live_id,
+                ticket,
+                enter_room,
+            } => {
+                if live_id != config.token.live_id
+                    || enter_room.enter_room_attach != config.token.enter_room_attach
+                    || !config.token.tickets.contains(&ticket)
+                {
+                    framed.send(ServerMessage::TicketInvalid).await?;
+                    continue;
+                }
+                framed
+                    .send(ServerMessage::EnterRoomAck {
+                        heartbeat_interval_ms: i64::try_from(config.heartbeat_interval.as_millis())
+                            .unwrap_or(i64::MAX),
+                    })
+                    .await?;
+                if index < config.drop_connections {
+                    break;
+                }
+                for message in &config.messages {
+                    framed.send(message.clone()).await?;
+                }
+            }
+            ClientCommand::Heartbeat { heartbeat, .. } => {
+                framed
+                    .send(ServerMessage::HeartbeatAck {
+                        client_timestamp_ms: heartbeat.client_timestamp_ms,
+                        client_sequence: heartbeat.sequence,
+                    })
+                    .await?
+            }
+            ClientCommand::UserExit { .. } => framed.send(ServerMessage::UserExitAck).await?,
+            ClientCommand::PushMessageAck | ClientCommand::Unknown(_) => {}
+        }
+    }
+    framed.close().await?;
+
+    Ok(())
+}
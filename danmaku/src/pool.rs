@@ -0,0 +1,355 @@
+use crate::{client::*, danmaku::Danmaku, global::*, websocket::WebSocket, Result};
+use futures::{stream::FusedStream, Stream, StreamExt};
+use std::{
+    borrow::Cow,
+    fmt,
+    marker::PhantomData,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+#[cfg(feature = "api")]
+use acfunliveapi::{client::ApiClient, pretend};
+
+#[cfg(feature = "default_ws_client")]
+use crate::websocket::WebSocketClient;
+
+#[cfg(feature = "default_ws_client")]
+pub type DefaultRoomPool = RoomPool<WebSocketClient>;
+
+/// Connects to the rooms of a `RoomPool` without borrowing the pool, got from `RoomPool::connector()`.
+pub struct RoomConnector<W> {
+    refresher: Arc<dyn TokenRefresher>,
+    server: String,
+    reconnect: Option<ReconnectPolicy>,
+    websocket: PhantomData<fn() -> W>,
+}
+
+impl<W> Clone for RoomConnector<W> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            refresher: self.refresher.clone(),
+            server: self.server.clone(),
+            reconnect: self.reconnect,
+            websocket: PhantomData,
+        }
+    }
+}
+
+impl<W> fmt::Debug for RoomConnector<W> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RoomConnector")
+            .field("server", &self.server)
+            .field("reconnect", &self.reconnect)
+            .finish()
+    }
+}
+
+impl<W: WebSocket> RoomConnector<W> {
+    /// Gets the token of the room from the `TokenRefresher` and connects to it.
+    pub async fn connect(&self, liver_uid: i64) -> std::result::Result<DanmakuClient<W>, W::Error> {
+        let token = self.refresher.refresh(liver_uid).await?;
+
+        self.connect_token(token).await
+    }
+
+    /// Connects to the room with the token.
+    pub async fn connect_token(
+        &self,
+        token: DanmakuToken,
+    ) -> std::result::Result<DanmakuClient<W>, W::Error> {
+        let mut client = DanmakuClient::<W>::with_server(token, self.server.as_str()).await?;
+        let _ = client
+            .set_reconnect_policy(self.reconnect)
+            .set_token_refresher(Some(self.refresher.clone()));
+
+        Ok(client)
+    }
+}
+
+/// Receives danmaku from many live rooms, keyed by `liver_uid`.
+///
+/// Rooms are connected by a `RoomConnector` outside the pool and inserted between polls. Every room
+/// gets its token from the shared `TokenRefresher`, which also refreshes the tickets of the room.
+/// A room is removed from the pool after its `DanmakuClient` ends, e.g. the live was closed.
+/// The stream is pending while the pool is empty and only ends after `close()`.
+pub struct RoomPool<W: WebSocket> {
+    refresher: Arc<dyn TokenRefresher>,
+    server: String,
+    reconnect: Option<ReconnectPolicy>,
+    rooms: Vec<DanmakuClient<W>>,
+    next: usize,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+impl<W: WebSocket> fmt::Debug for RoomPool<W> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RoomPool")
+            .field("server", &self.server)
+            .field("reconnect", &self.reconnect)
+            .field("rooms", &self.liver_uids().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<W: WebSocket> RoomPool<W> {
+    #[inline]
+    pub fn new(refresher: Arc<dyn TokenRefresher>) -> Self {
+        Self {
+            refresher,
+            server: DANMAKU_SERVER.to_string(),
+            reconnect: None,
+            rooms: Vec::new(),
+            next: 0,
+            waker: None,
+            closed: false,
+        }
+    }
+
+    #[cfg(feature = "api")]
+    #[inline]
+    pub fn from_api_client<C>(client: ApiClient<C>) -> Self
+    where
+        C: pretend::client::Client + Send + Sync + 'static,
+    {
+        Self::new(Arc::new(client))
+    }
+
+    /// Sets the danmaku server of the rooms added later.
+    #[inline]
+    pub fn set_server<'a>(&mut self, server: impl Into<Cow<'a, str>>) -> &mut Self {
+        self.server = server.into().into_owned();
+        self
+    }
+
+    #[inline]
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    /// Sets the reconnect policy of the rooms added later.
+    #[inline]
+    pub fn set_reconnect_policy(&mut self, policy: Option<ReconnectPolicy>) -> &mut Self {
+        self.reconnect = policy;
+        self
+    }
+
+    #[inline]
+    pub const fn reconnect_policy(&self) -> Option<&ReconnectPolicy> {
+        self.reconnect.as_ref()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.rooms.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.rooms.is_empty()
+    }
+
+    #[inline]
+    pub fn contains(&self, liver_uid: i64) -> bool {
+        self.rooms.iter().any(|r| r.liver_uid() == liver_uid)
+    }
+
+    #[inline]
+    pub fn liver_uids(&self) -> impl Iterator<Item = i64> + '_ {
+        self.rooms.iter().map(|r| r.liver_uid())
+    }
+
+    #[inline]
+    pub fn room(&self, liver_uid: i64) -> Option<&DanmakuClient<W>> {
+        self.rooms.iter().find(|r| r.liver_uid() == liver_uid)
+    }
+
+    #[inline]
+    pub fn room_mut(&mut self, liver_uid: i64) -> Option<&mut DanmakuClient<W>> {
+        self.rooms.iter_mut().find(|r| r.liver_uid() == liver_uid)
+    }
+
+    /// Returns a connector with the refresher, the server and the reconnect policy of the pool.
+    #[inline]
+    pub fn connector(&self) -> RoomConnector<W> {
+        RoomConnector {
+            refresher: self.refresher.clone(),
+            server: self.server.clone(),
+            reconnect: self.reconnect,
+            websocket: PhantomData,
+        }
+    }
+
+    /// Adds the connected room, returns false and drops the client if the room is already in the
+    /// pool or the pool was closed.
+    pub fn insert(&mut self, client: DanmakuClient<W>) -> bool {
+        if self.closed || self.contains(client.liver_uid()) {
+            return false;
+        }
+        self.rooms.push(client);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+
+        true
+    }
+
+    /// Leaves the room and closes its connection, returns false if the room isn't in the pool.
+    pub async fn remove_room(&mut self, liver_uid: i64) -> Result<bool> {
+        match self.rooms.iter().position(|r| r.liver_uid() == liver_uid) {
+            Some(index) => {
+                let mut client = self.rooms.remove(index);
+                client.close().await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Leaves all rooms and ends the stream, rooms can't be inserted after closing.
+    pub async fn close(&mut self) -> Result<()> {
+        self.closed = true;
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+        for mut client in self.rooms.drain(..) {
+            client.close().await?;
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+
+impl<W: WebSocket + 'static> Stream for RoomPool<W> {
+    type Item = (i64, std::result::Result<Danmaku, W::Error>);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.closed {
+            return Poll::Ready(None);
+        }
+        // polls the rooms in turn, starting from the one after the last ready room
+        let mut polled = 0;
+        while polled < self.rooms.len() {
+            let index = (self.next + polled) % self.rooms.len();
+            match self.rooms[index].poll_next_unpin(cx) {
+                Poll::Ready(Some(item)) => {
+                    self.next = index + 1;
+                    return Poll::Ready(Some((self.rooms[index].liver_uid(), item)));
+                }
+                Poll::Ready(None) => {
+                    let client = self.rooms.remove(index);
+                    log::trace!("the room of liver {} was closed", client.liver_uid());
+                    if index < self.next {
+                        self.next -= 1;
+                    }
+                }
+                Poll::Pending => polled += 1,
+            }
+        }
+
+        // rooms inserted later wake the pool
+        self.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<W: WebSocket + 'static> FusedStream for RoomPool<W> {
+    #[inline]
+    fn is_terminated(&self) -> bool {
+        self.closed
+    }
+}
+
+#[cfg(feature = "default_ws_client")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{acproto, danmaku::*, mock::*};
+    use async_trait::async_trait;
+    use futures::FutureExt;
+    use prost::Message;
+    use std::{collections::HashMap, time::Duration};
+    use tokio::time::timeout;
+
+    struct MockRefresher(DanmakuToken);
+
+    #[async_trait]
+    impl TokenRefresher for MockRefresher {
+        async fn refresh(&self, liver_uid: i64) -> Result<DanmakuToken> {
+            Ok(DanmakuToken {
+                liver_uid,
+                ..self.0.clone()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_room_pool() -> Result<()> {
+        let server = MockDanmakuServer::bind().await?;
+        let url = server.url();
+        let token = server.token();
+        let comment = acproto::CommonActionSignalComment {
+            content: "comment".to_string(),
+            ..Default::default()
+        };
+        let server = server
+            .push_action_signal(COMMENT, comment.encode_to_vec())
+            .push_status_changed(acproto::ZtLiveScStatusChanged {
+                r#type: acproto::zt_live_sc_status_changed::Type::LiveClosed.into(),
+                ..Default::default()
+            })
+            .spawn();
+
+        let mut pool = DefaultRoomPool::new(Arc::new(MockRefresher(token)));
+        let _ = pool.set_server(url);
+        // an empty pool waits for rooms instead of ending
+        assert!(pool.next().now_or_never().is_none());
+        assert!(!pool.is_terminated());
+
+        let connector = pool.connector();
+        assert!(pool.insert(connector.connect(1).await?));
+        assert!(pool.insert(connector.connect(2).await?));
+        assert!(!pool.insert(connector.connect(2).await?));
+        assert!(pool.insert(connector.connect(3).await?));
+        assert!(pool.remove_room(3).await?);
+        assert!(!pool.remove_room(3).await?);
+        assert_eq!(pool.liver_uids().collect::<Vec<_>>(), vec![1, 2]);
+
+        let mut received: HashMap<i64, Vec<Danmaku>> = HashMap::new();
+        while received.values().map(Vec::len).sum::<usize>() < 4 {
+            let (liver_uid, danmaku) = timeout(Duration::from_secs(10), pool.next())
+                .await
+                .expect("timeout when getting danmaku from the room pool")
+                .expect("the room pool ended before closing");
+            received.entry(liver_uid).or_default().push(danmaku?);
+        }
+        assert!(timeout(Duration::from_millis(100), pool.next())
+            .await
+            .is_err());
+        assert!(pool.is_empty());
+        pool.close().await?;
+        assert!(pool.is_terminated());
+        assert!(pool.next().await.is_none());
+        assert!(!pool.insert(connector.connect(1).await?));
+        let expected = vec![
+            Danmaku::ActionSignal(vec![ActionSignal::Comment(comment)]),
+            Danmaku::StatusChanged(LiveStatus::Closed),
+        ];
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[&1], expected);
+        assert_eq!(received[&2], expected);
+        server.abort();
+
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{client::DanmakuToken, danmaku::*, global::*, Error, Result};
+use crate::{client::DanmakuToken, danmaku::*, global::*, record::Recorder, Error, Result};
 use aes::Aes128;
 use asynchronous_codec::{BytesMut, Decoder, Encoder};
 use block_modes::{block_padding::Pkcs7, BlockMode, Cbc};
@@ -41,6 +41,111 @@ fn decrypt(cipher_text: &[u8], key: &[u8]) -> Result<Vec<u8>> {
     Ok(cipher.decrypt_vec(cipher_text)?)
 }
 
+#[inline]
+fn packet_key<'a>(
+    mode: acproto::packet_header::EncryptionMode,
+    security_key: &'a [u8],
+    session_key: Option<&'a [u8]>,
+) -> Result<Option<&'a [u8]>> {
+    match mode {
+        acproto::packet_header::EncryptionMode::KEncryptionNone => Ok(None),
+        acproto::packet_header::EncryptionMode::KEncryptionServiceToken => Ok(Some(security_key)),
+        acproto::packet_header::EncryptionMode::KEncryptionSessionKey => {
+            session_key.map(Some).ok_or(Error::NoSessionKey)
+        }
+    }
+}
+
+pub(crate) fn encode_packet(
+    mut header: acproto::PacketHeader,
+    payload: &[u8],
+    security_key: &[u8],
+    session_key: Option<&[u8]>,
+    dst: &mut BytesMut,
+) -> Result<()> {
+    header.decoded_payload_len = u32::try_from(payload.len())?;
+    let encrypted = match packet_key(header.encryption_mode(), security_key, session_key)? {
+        Some(key) => encrypt(payload, key)?,
+        None => payload.to_vec(),
+    };
+    let header = header.encode_to_vec();
+
+    dst.reserve(3 * U32_LENGTH + header.len() + encrypted.len());
+    dst.extend_from_slice(&PROTO_MAGIC);
+    dst.extend_from_slice(&(u32::try_from(header.len())?).to_be_bytes());
+    dst.extend_from_slice(&(u32::try_from(encrypted.len())?).to_be_bytes());
+    dst.extend_from_slice(&header);
+    dst.extend_from_slice(&encrypted);
+
+    Ok(())
+}
+
+pub(crate) fn decode_packet(
+    src: &mut BytesMut,
+    security_key: &[u8],
+    session_key: Option<&[u8]>,
+) -> Result<Option<(acproto::PacketHeader, Vec<u8>)>> {
+    if src.len() < 3 * U32_LENGTH {
+        return Ok(None);
+    }
+
+    let header_length = usize::try_from(u32::from_be_bytes(
+        src[U32_LENGTH..2 * U32_LENGTH].try_into()?,
+    ))?;
+    let body_length = usize::try_from(u32::from_be_bytes(
+        src[2 * U32_LENGTH..3 * U32_LENGTH].try_into()?,
+    ))?;
+    if src.len() < 3 * U32_LENGTH + header_length + body_length {
+        src.reserve(3 * U32_LENGTH + header_length + body_length - src.len());
+        return Ok(None);
+    }
+
+    src.advance(3 * U32_LENGTH);
+    let header = acproto::PacketHeader::decode(src.split_to(header_length))?;
+    let body = src.split_to(body_length);
+    let payload = match packet_key(header.encryption_mode(), security_key, session_key)? {
+        Some(key) => decrypt(&body, key)?,
+        None => body.to_vec(),
+    };
+    let payload_len = usize::try_from(header.decoded_payload_len)?;
+    if payload.len() != payload_len {
+        return Err(Error::ProtoDataLengthError(
+            "payload length",
+            payload_len,
+            payload.len(),
+        ));
+    }
+
+    Ok(Some((header, payload)))
+}
+
+pub(crate) fn push_message(message: acproto::ZtLiveScMessage) -> Result<Option<Danmaku>> {
+    let payload =
+        if message.compression_type() == acproto::zt_live_sc_message::CompressionType::Gzip {
+            let mut reader = GzDecoder::new(message.payload.as_slice());
+            let mut buf = Vec::new();
+            let _ = reader.read_to_end(&mut buf)?;
+            buf
+        } else {
+            message.payload
+        };
+    match message.message_type.as_str() {
+        ACTION_SIGNAL => Ok(Some(action_signal(&payload)?.into())),
+        STATE_SIGNAL => Ok(Some(state_signal(&payload)?.into())),
+        NOTIFY_SIGNAL => Ok(Some(notify_signal(&payload)?.into())),
+        STATUS_CHANGED => Ok(Some(Danmaku::StatusChanged(
+            acproto::ZtLiveScStatusChanged::decode(payload.as_slice())?.into(),
+        ))),
+        _ => {
+            log::trace!(
+                "unknown ZtLiveScMessage message_type: {}",
+                message.message_type
+            );
+            Ok(None)
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub(crate) enum SendMessage {
     RegisterRequest,
@@ -59,12 +164,13 @@ pub(crate) enum ReceiveMessage {
     Interval(u64),
     PushMessage,
     EnterRoom,
-    PushAndStop,
+    TicketsExhausted,
+    StatusChanged(LiveStatus),
     Stop,
     Close,
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Debug, Default)]
 pub(crate) struct DanmakuProto {
     pub(crate) user_id: i64,
     pub(crate) liver_uid: i64,
@@ -80,6 +186,10 @@ pub(crate) struct DanmakuProto {
     header_seq_id: i64,
     heartbeat_seq_id: i64,
     ticket_index: usize,
+    invalid_ticket_count: usize,
+    pub(crate) recorder: Option<Recorder>,
+    /// The error of the recorder, which was dropped after it failed.
+    pub(crate) record_error: Option<Error>,
 }
 
 impl DanmakuProto {
@@ -131,6 +241,38 @@ impl DanmakuProto {
         cmd.encode_to_vec()
     }
 
+    #[inline]
+    pub(crate) fn reset(&mut self) {
+        self.app_id = 0;
+        self.instance_id = 0;
+        self.session_key = None;
+        self.seq_id = 1;
+        self.header_seq_id = 1;
+        self.heartbeat_seq_id = 0;
+    }
+
+    pub(crate) fn update_tickets(&mut self, token: DanmakuToken) -> Result<()> {
+        if token.tickets.is_empty() || token.enter_room_attach.is_empty() {
+            return Err(Error::InvalidToken);
+        }
+        if token.live_id != self.live_id {
+            log::trace!(
+                "the live ID changed from {} to {} after refreshing the danmaku token",
+                self.live_id,
+                token.live_id
+            );
+            self.live_id = token.live_id;
+        }
+        self.security_key = base64::decode(&token.security_key)?;
+        self.service_token = token.service_token;
+        self.enter_room_attach = token.enter_room_attach;
+        self.tickets = token.tickets;
+        self.ticket_index = 0;
+        self.invalid_ticket_count = 0;
+
+        Ok(())
+    }
+
     #[inline]
     pub(crate) fn register_response(&mut self, payload: &acproto::DownstreamPayload) -> Result<()> {
         let resp = acproto::RegisterResponse::decode(payload.payload_data.as_slice())?;
@@ -152,6 +294,7 @@ impl DanmakuProto {
                     ENTER_ROOM_ACK => {
                         let enter_room =
                             acproto::ZtLiveCsEnterRoomAck::decode(cmd.payload.as_slice())?;
+                        self.invalid_ticket_count = 0;
                         let interval = if enter_room.heartbeat_interval_ms > 0 {
                             u64::try_from(enter_room.heartbeat_interval_ms)?
                         } else {
@@ -172,49 +315,22 @@ impl DanmakuProto {
             UNREGISTER => Ok(Some(ReceiveMessage::Close)),
             PUSH_MESSAGE => {
                 let message = acproto::ZtLiveScMessage::decode(stream.payload_data.as_slice())?;
-                let payload = if message.compression_type()
-                    == acproto::zt_live_sc_message::CompressionType::Gzip
-                {
-                    let mut reader = GzDecoder::new(message.payload.as_slice());
-                    let mut buf = Vec::new();
-                    let _ = reader.read_to_end(&mut buf)?;
-                    buf
-                } else {
-                    message.payload
-                };
-                match message.message_type.as_str() {
-                    ACTION_SIGNAL => Ok(Some(ReceiveMessage::Danmaku(
-                        action_signal(&payload)?.into(),
-                    ))),
-                    STATE_SIGNAL => Ok(Some(ReceiveMessage::Danmaku(
-                        state_signal(&payload)?.into(),
-                    ))),
-                    NOTIFY_SIGNAL => Ok(Some(ReceiveMessage::Danmaku(
-                        notify_signal(&payload)?.into(),
-                    ))),
-                    STATUS_CHANGED => {
-                        let status = acproto::ZtLiveScStatusChanged::decode(payload.as_slice())?;
-                        if status.r#type() == acproto::zt_live_sc_status_changed::Type::LiveClosed
-                            || status.r#type()
-                                == acproto::zt_live_sc_status_changed::Type::LiveBanned
-                        {
-                            Ok(Some(ReceiveMessage::PushAndStop))
-                        } else {
-                            Ok(Some(ReceiveMessage::PushMessage))
-                        }
-                    }
-                    TICKET_INVALID => {
-                        log::trace!("danmaku ticket is invalid");
+                if message.message_type == TICKET_INVALID {
+                    log::trace!("danmaku ticket is invalid");
+                    self.invalid_ticket_count += 1;
+                    return if self.invalid_ticket_count >= self.tickets.len() {
+                        Ok(Some(ReceiveMessage::TicketsExhausted))
+                    } else {
                         self.ticket_index = (self.ticket_index + 1) % self.tickets.len();
                         Ok(Some(ReceiveMessage::EnterRoom))
+                    };
+                }
+                match push_message(message)? {
+                    Some(Danmaku::StatusChanged(status)) => {
+                        Ok(Some(ReceiveMessage::StatusChanged(status)))
                     }
-                    _ => {
-                        log::trace!(
-                            "unknown ZtLiveScMessage message_type: {}",
-                            message.message_type
-                        );
-                        Ok(Some(ReceiveMessage::PushMessage))
-                    }
+                    Some(danmaku) => Ok(Some(ReceiveMessage::Danmaku(danmaku))),
+                    None => Ok(Some(ReceiveMessage::PushMessage)),
                 }
             }
             _ => {
@@ -247,7 +363,7 @@ impl Encoder for DanmakuProto {
 
     // https://github.com/wpscott/AcFunDanmaku/tree/master/AcFunDanmu
     fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<()> {
-        let (mut header, payload) = match item {
+        let (header, payload) = match item {
             SendMessage::RegisterRequest => acproto::RegisterRequest::generate(self),
             SendMessage::UnregisterRequest => acproto::UnregisterRequest::generate(self),
             SendMessage::ZtLiveCsEnterRoom => acproto::ZtLiveCsEnterRoom::generate(self),
@@ -257,31 +373,13 @@ impl Encoder for DanmakuProto {
             SendMessage::ZtLiveCsUserExit => acproto::ZtLiveCsUserExit::generate(self),
         };
 
-        let payload = payload.encode_to_vec();
-        header.decoded_payload_len = u32::try_from(payload.len())?;
-        let encrypted = match header.encryption_mode() {
-            acproto::packet_header::EncryptionMode::KEncryptionNone => payload,
-            acproto::packet_header::EncryptionMode::KEncryptionServiceToken => {
-                encrypt(&payload, &self.security_key)?
-            }
-            acproto::packet_header::EncryptionMode::KEncryptionSessionKey => {
-                if let Some(key) = &self.session_key {
-                    encrypt(&payload, key)?
-                } else {
-                    return Err(Error::NoSessionKey);
-                }
-            }
-        };
-        let header = header.encode_to_vec();
-
-        dst.reserve(3 * U32_LENGTH + header.len() + encrypted.len());
-        dst.extend_from_slice(&PROTO_MAGIC);
-        dst.extend_from_slice(&(u32::try_from(header.len())?).to_be_bytes());
-        dst.extend_from_slice(&(u32::try_from(encrypted.len())?).to_be_bytes());
-        dst.extend_from_slice(&header);
-        dst.extend_from_slice(&encrypted);
-
-        Ok(())
+        encode_packet(
+            header,
+            &payload.encode_to_vec(),
+            &self.security_key,
+            self.session_key.as_deref(),
+            dst,
+        )
     }
 }
 
@@ -292,51 +390,25 @@ impl Decoder for DanmakuProto {
 
     // https://github.com/wpscott/AcFunDanmaku/tree/master/AcFunDanmu
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>> {
-        if src.len() < 3 * U32_LENGTH {
-            return Ok(None);
-        }
-
-        let header_length = usize::try_from(u32::from_be_bytes(
-            src[U32_LENGTH..2 * U32_LENGTH].try_into()?,
-        ))?;
-        let body_length = usize::try_from(u32::from_be_bytes(
-            src[2 * U32_LENGTH..3 * U32_LENGTH].try_into()?,
-        ))?;
-        if src.len() < 3 * U32_LENGTH + header_length + body_length {
-            src.reserve(3 * U32_LENGTH + header_length + body_length - src.len());
-            return Ok(None);
-        }
-
-        src.advance(3 * U32_LENGTH);
-        let header = acproto::PacketHeader::decode(src.split_to(header_length))?;
+        let (header, payload) =
+            match decode_packet(src, &self.security_key, self.session_key.as_deref())? {
+                Some(packet) => packet,
+                None => return Ok(None),
+            };
         self.app_id = header.app_id;
         self.header_seq_id = header.seq_id;
-        let body = src.split_to(body_length);
-        let decrypted: Vec<u8>;
-        let payload = match header.encryption_mode() {
-            acproto::packet_header::EncryptionMode::KEncryptionNone => body.as_ref(),
-            acproto::packet_header::EncryptionMode::KEncryptionServiceToken => {
-                decrypted = decrypt(&body, &self.security_key)?;
-                decrypted.as_slice()
+        let stream = acproto::DownstreamPayload::decode(payload.as_slice())?;
+        if let Some(recorder) = &self.recorder {
+            // a failed recording mustn't lose the message or break the connection
+            if let Err(e) = recorder.record(&stream) {
+                log::trace!(
+                    "failed to record the danmaku session, stop recording: {}",
+                    e
+                );
+                self.recorder = None;
+                self.record_error = Some(e);
             }
-            acproto::packet_header::EncryptionMode::KEncryptionSessionKey => {
-                decrypted = if let Some(key) = &self.session_key {
-                    decrypt(&body, key)?
-                } else {
-                    return Err(Error::NoSessionKey);
-                };
-                decrypted.as_slice()
-            }
-        };
-        let payload_len = usize::try_from(header.decoded_payload_len)?;
-        if payload.len() != payload_len {
-            return Err(Error::ProtoDataLengthError(
-                "payload length",
-                payload_len,
-                payload.len(),
-            ));
         }
-        let stream = acproto::DownstreamPayload::decode(payload)?;
 
         self.danmaku(&stream)
     }
@@ -0,0 +1,374 @@
+//! Records the raw danmaku session and replays it offline.
+//!
+//! A recording starts with `RECORD_MAGIC`, followed by records of a big-endian `i64` receive time
+//! in milliseconds since the Unix epoch, a big-endian `u32` length and the encoded `DownstreamPayload`.
+
+use crate::{acproto, danmaku::Danmaku, global::*, proto::push_message, Error, Result};
+use blocking::Unblock;
+use futures::{future, ready, stream::FusedStream, AsyncWrite, Future, Stream};
+use futures_timer::Delay;
+use prost::Message;
+use std::{
+    convert::TryFrom,
+    fmt,
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant, SystemTime},
+};
+
+pub const RECORD_MAGIC: [u8; 8] = *b"ACDMREC1";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    /// Milliseconds since the Unix epoch when the payload was received.
+    pub time: i64,
+    pub payload: acproto::DownstreamPayload,
+}
+
+impl Record {
+    /// Decodes the `Danmaku` in the record, returns `None` if it isn't a pushed danmaku message.
+    #[inline]
+    pub fn danmaku(&self) -> Result<Option<Danmaku>> {
+        if self.payload.command == PUSH_MESSAGE {
+            push_message(acproto::ZtLiveScMessage::decode(
+                self.payload.payload_data.as_slice(),
+            )?)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+struct RecorderInner {
+    buffer: Vec<u8>,
+    writer: Unblock<Box<dyn Write + Send>>,
+}
+
+/// Records `DownstreamPayload`s, clones share the same recording.
+///
+/// Records are buffered in memory and written on a thread pool while the `DanmakuClient` is polled,
+/// `flush()` waits until all buffered records are written.
+#[derive(Clone)]
+pub struct Recorder(Arc<Mutex<RecorderInner>>);
+
+impl fmt::Debug for Recorder {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Recorder")
+    }
+}
+
+impl Recorder {
+    pub fn new(writer: impl Write + Send + 'static) -> Result<Self> {
+        let writer: Box<dyn Write + Send> = Box::new(writer);
+
+        Ok(Self(Arc::new(Mutex::new(RecorderInner {
+            buffer: RECORD_MAGIC.to_vec(),
+            writer: Unblock::new(writer),
+        }))))
+    }
+
+    #[inline]
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    /// Buffers the record of the payload without blocking.
+    pub fn record(&self, payload: &acproto::DownstreamPayload) -> Result<()> {
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
+        let payload = payload.encode_to_vec();
+        let length = u32::try_from(payload.len())?;
+        let buffer = &mut self.0.lock().expect("the recorder was poisoned").buffer;
+        buffer.extend_from_slice(&time.to_be_bytes());
+        buffer.extend_from_slice(&length.to_be_bytes());
+        buffer.extend_from_slice(&payload);
+
+        Ok(())
+    }
+
+    /// Writes the buffered records and flushes the writer.
+    pub(crate) fn poll_flush(&self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let mut inner = self.0.lock().expect("the recorder was poisoned");
+        let RecorderInner { buffer, writer } = &mut *inner;
+        while !buffer.is_empty() {
+            let n = ready!(Pin::new(&mut *writer).poll_write(cx, buffer))
+                .map_err(Error::RecordError)?;
+            if n == 0 {
+                return Poll::Ready(Err(Error::RecordError(ErrorKind::WriteZero.into())));
+            }
+            let _ = buffer.drain(..n);
+        }
+        Pin::new(writer).poll_flush(cx).map_err(Error::RecordError)
+    }
+
+    /// Waits until all buffered records are written.
+    #[inline]
+    pub async fn flush(&self) -> Result<()> {
+        future::poll_fn(|cx| self.poll_flush(cx)).await
+    }
+}
+
+#[derive(Debug)]
+pub struct RecordReader<R> {
+    reader: R,
+}
+
+impl RecordReader<BufReader<File>> {
+    #[inline]
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> RecordReader<R> {
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0u8; RECORD_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != RECORD_MAGIC {
+            return Err(Error::InvalidRecord);
+        }
+
+        Ok(Self { reader })
+    }
+
+    fn read_record(&mut self) -> Result<Option<Record>> {
+        let mut time = [0u8; 8];
+        match self.reader.read_exact(&mut time) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let mut length = [0u8; 4];
+        self.reader.read_exact(&mut length)?;
+        let mut payload = vec![0u8; usize::try_from(u32::from_be_bytes(length))?];
+        self.reader.read_exact(&mut payload)?;
+
+        Ok(Some(Record {
+            time: i64::from_be_bytes(time),
+            payload: acproto::DownstreamPayload::decode(payload.as_slice())?,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for RecordReader<R> {
+    type Item = Result<Record>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Replays the danmaku in a recording, keeping the original intervals divided by the speed.
+///
+/// Records are read with the blocking `Read` inside `poll_next()`, which is fine for local files
+/// and in-memory data, but readers which may block for long, e.g. network files, stall the executor.
+#[derive(Debug)]
+pub struct Replay<R> {
+    records: RecordReader<R>,
+    speed: Option<f64>,
+    start: Option<(Instant, i64)>,
+    delay: Option<Delay>,
+    pending: Option<Danmaku>,
+    terminated: bool,
+}
+
+impl Replay<BufReader<File>> {
+    #[inline]
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(RecordReader::open(path)?))
+    }
+}
+
+impl<R: Read> Replay<R> {
+    #[inline]
+    pub fn new(records: RecordReader<R>) -> Self {
+        Self {
+            records,
+            speed: Some(1.0),
+            start: None,
+            delay: None,
+            pending: None,
+            terminated: false,
+        }
+    }
+
+    /// Sets the speed of the replay, `None` or a non-positive speed replays without waiting.
+    #[inline]
+    pub fn speed(mut self, speed: Option<f64>) -> Self {
+        self.speed = speed.filter(|s| *s > 0.0);
+        self
+    }
+
+    fn wait(&mut self, time: i64) -> Option<Duration> {
+        let speed = self.speed?;
+        let (start, first) = *self.start.get_or_insert((Instant::now(), time));
+        let offset =
+            Duration::from_millis(u64::try_from(time - first).unwrap_or_default()).div_f64(speed);
+        offset.checked_sub(start.elapsed()).filter(|d| !d.is_zero())
+    }
+}
+
+impl<R: Read + Unpin> Stream for Replay<R> {
+    type Item = Result<Danmaku>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(delay) = self.delay.as_mut() {
+                ready!(Pin::new(delay).poll(cx));
+                self.delay = None;
+                if let Some(danmaku) = self.pending.take() {
+                    return Poll::Ready(Some(Ok(danmaku)));
+                }
+            }
+            if self.terminated {
+                return Poll::Ready(None);
+            }
+            let record = match self.records.next() {
+                Some(Ok(record)) => record,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {
+                    self.terminated = true;
+                    return Poll::Ready(None);
+                }
+            };
+            match record.danmaku() {
+                Ok(Some(danmaku)) => match self.wait(record.time) {
+                    Some(wait) => {
+                        self.delay = Some(Delay::new(wait));
+                        self.pending = Some(danmaku);
+                    }
+                    None => return Poll::Ready(Some(Ok(danmaku))),
+                },
+                Ok(None) => {}
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+    }
+}
+
+impl<R: Read + Unpin> FusedStream for Replay<R> {
+    #[inline]
+    fn is_terminated(&self) -> bool {
+        self.terminated && self.pending.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::danmaku::*;
+    use futures::TryStreamExt;
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().expect("the buffer was poisoned").write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn comment_payload(content: &str) -> acproto::DownstreamPayload {
+        let comment = acproto::CommonActionSignalComment {
+            content: content.to_string(),
+            ..Default::default()
+        };
+        let signal = acproto::ZtLiveScActionSignal {
+            item: vec![acproto::ZtLiveActionSignalItem {
+                signal_type: COMMENT.to_string(),
+                payload: vec![comment.encode_to_vec()],
+            }],
+        };
+        let message = acproto::ZtLiveScMessage {
+            message_type: ACTION_SIGNAL.to_string(),
+            payload: signal.encode_to_vec(),
+            ..Default::default()
+        };
+        acproto::DownstreamPayload {
+            command: PUSH_MESSAGE.to_string(),
+            payload_data: message.encode_to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() -> Result<()> {
+        let buf = SharedBuf::default();
+        let recorder = Recorder::new(buf.clone())?;
+        recorder.record(&acproto::DownstreamPayload {
+            command: KEEP_ALIVE.to_string(),
+            ..Default::default()
+        })?;
+        recorder.record(&comment_payload("first"))?;
+        recorder.record(&comment_payload("second"))?;
+        recorder.flush().await?;
+        let data = buf.0.lock().expect("the buffer was poisoned").clone();
+
+        let records = RecordReader::new(data.as_slice())?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].payload, comment_payload("first"));
+        assert!(records[0].time <= records[2].time);
+
+        let danmaku: Vec<Danmaku> = Replay::new(RecordReader::new(data.as_slice())?)
+            .speed(Some(100.0))
+            .try_collect()
+            .await?;
+        let comment = |content: &str| {
+            Danmaku::ActionSignal(vec![ActionSignal::Comment(
+                acproto::CommonActionSignalComment {
+                    content: content.to_string(),
+                    ..Default::default()
+                },
+            )])
+        };
+        assert_eq!(danmaku, vec![comment("first"), comment("second")]);
+
+        assert!(matches!(
+            RecordReader::new(&b"not a record"[..]),
+            Err(Error::InvalidRecord)
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replay_speed() -> Result<()> {
+        let mut data = RECORD_MAGIC.to_vec();
+        for time in [0i64, 200] {
+            let payload = comment_payload("comment").encode_to_vec();
+            data.extend_from_slice(&time.to_be_bytes());
+            data.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            data.extend_from_slice(&payload);
+        }
+
+        let now = Instant::now();
+        let danmaku: Vec<Danmaku> = Replay::new(RecordReader::new(data.as_slice())?)
+            .speed(Some(2.0))
+            .try_collect()
+            .await?;
+        assert_eq!(danmaku.len(), 2);
+        assert!(now.elapsed() >= Duration::from_millis(100));
+
+        let now = Instant::now();
+        let danmaku: Vec<Danmaku> = Replay::new(RecordReader::new(data.as_slice())?)
+            .speed(None)
+            .try_collect()
+            .await?;
+        assert_eq!(danmaku.len(), 2);
+        assert!(now.elapsed() < Duration::from_millis(100));
+
+        Ok(())
+    }
+}
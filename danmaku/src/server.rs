@@ -0,0 +1,500 @@
+//! The server side of the danmaku protocol, for building fake danmaku servers.
+
+use crate::{
+    acproto::{self, packet_header::EncryptionMode},
+    global::*,
+    proto::{decode_packet, encode_packet},
+    Error, Result,
+};
+use asynchronous_codec::{BytesMut, Decoder, Encoder};
+use flate2::{write::GzEncoder, Compression};
+use prost::Message;
+use rand::{distributions::Standard, Rng};
+use std::{io::Write, time::SystemTime};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClientCommand {
+    Register {
+        service_token: String,
+        request: acproto::RegisterRequest,
+    },
+    Unregister,
+    KeepAlive(acproto::KeepAliveRequest),
+    EnterRoom {
+        live_id: String,
+        ticket: String,
+        enter_room: acproto::ZtLiveCsEnterRoom,
+    },
+    Heartbeat {
+        live_id: String,
+        ticket: String,
+        heartbeat: acproto::ZtLiveCsHeartbeat,
+    },
+    UserExit {
+        live_id: String,
+        ticket: String,
+    },
+    /// The client acknowledged a pushed message.
+    PushMessageAck,
+    Unknown(acproto::UpstreamPayload),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientMessage {
+    pub header: acproto::PacketHeader,
+    pub command: ClientCommand,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServerMessage {
+    /// Encrypted with the security key, the codec uses the session key and instance ID afterwards.
+    RegisterResponse {
+        instance_id: i64,
+        session_key: Vec<u8>,
+    },
+    Unregister,
+    KeepAlive,
+    EnterRoomAck {
+        heartbeat_interval_ms: i64,
+    },
+    HeartbeatAck {
+        client_timestamp_ms: i64,
+        client_sequence: i64,
+    },
+    UserExitAck,
+    ActionSignal(acproto::ZtLiveScActionSignal),
+    StateSignal(acproto::ZtLiveScStateSignal),
+    NotifySignal(acproto::ZtLiveScNotifySignal),
+    StatusChanged(acproto::ZtLiveScStatusChanged),
+    TicketInvalid,
+    /// Tells the client to stop getting danmaku.
+    Stop,
+    Push(acproto::ZtLiveScMessage),
+    Raw(EncryptionMode, acproto::DownstreamPayload),
+}
+
+impl ServerMessage {
+    #[inline]
+    pub fn register_response() -> Self {
+        Self::RegisterResponse {
+            instance_id: rand::thread_rng().gen_range(1..i64::MAX),
+            session_key: rand::thread_rng()
+                .sample_iter(Standard)
+                .take(aes::BLOCK_SIZE)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DanmakuServerProto {
+    security_key: Vec<u8>,
+    session_key: Option<Vec<u8>>,
+    app_id: i32,
+    instance_id: i64,
+    user_id: i64,
+    seq_id: i64,
+    live_id: String,
+    ticket: String,
+    gzip: bool,
+}
+
+impl DanmakuServerProto {
+    /// `security_key` is base64 encoded, the same as `DanmakuToken::security_key`.
+    #[inline]
+    pub fn new(security_key: impl AsRef<[u8]>) -> Result<Self> {
+        Ok(Self {
+            security_key: base64::decode(security_key)?,
+            session_key: None,
+            app_id: 13,
+            instance_id: 0,
+            user_id: 0,
+            seq_id: 1,
+            live_id: String::new(),
+            ticket: String::new(),
+            gzip: false,
+        })
+    }
+
+    #[inline]
+    pub fn session_key(&self) -> Option<&[u8]> {
+        self.session_key.as_deref()
+    }
+
+    #[inline]
+    pub fn set_session_key(&mut self, session_key: Option<Vec<u8>>) -> &mut Self {
+        self.session_key = session_key;
+        self
+    }
+
+    #[inline]
+    pub const fn user_id(&self) -> i64 {
+        self.user_id
+    }
+
+    #[inline]
+    pub const fn instance_id(&self) -> i64 {
+        self.instance_id
+    }
+
+    /// The live ID the client entered.
+    #[inline]
+    pub fn live_id(&self) -> &str {
+        &self.live_id
+    }
+
+    /// The ticket the client used to enter the room.
+    #[inline]
+    pub fn ticket(&self) -> &str {
+        &self.ticket
+    }
+
+    /// Compresses the payload of pushed messages with gzip.
+    #[inline]
+    pub fn set_gzip(&mut self, gzip: bool) -> &mut Self {
+        self.gzip = gzip;
+        self
+    }
+
+    fn push(&self, message_type: &str, payload: Vec<u8>) -> Result<acproto::ZtLiveScMessage> {
+        let mut message = acproto::ZtLiveScMessage {
+            message_type: message_type.to_string(),
+            live_id: self.live_id.clone(),
+            ticket: self.ticket.clone(),
+            server_timestamp_ms: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as i64,
+            ..Default::default()
+        };
+        if self.gzip {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&payload)?;
+            message.payload = encoder.finish()?;
+            message.set_compression_type(acproto::zt_live_sc_message::CompressionType::Gzip);
+        } else {
+            message.payload = payload;
+        }
+
+        Ok(message)
+    }
+
+    fn downstream(&self, command: &str, payload_data: Vec<u8>) -> acproto::DownstreamPayload {
+        acproto::DownstreamPayload {
+            command: command.to_string(),
+            seq_id: self.seq_id,
+            payload_data,
+            sub_biz: SUB_BIZ.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    fn cmd_ack(&self, cmd_ack_type: &str, payload: Vec<u8>) -> acproto::DownstreamPayload {
+        let ack = acproto::ZtLiveCsCmdAck {
+            cmd_ack_type: cmd_ack_type.to_string(),
+            payload,
+            ..Default::default()
+        };
+        self.downstream(GLOBAL_CS_CMD, ack.encode_to_vec())
+    }
+
+    #[inline]
+    fn push_downstream(&self, message: &acproto::ZtLiveScMessage) -> acproto::DownstreamPayload {
+        self.downstream(PUSH_MESSAGE, message.encode_to_vec())
+    }
+
+    fn command(&mut self, payload: acproto::UpstreamPayload) -> Result<ClientCommand> {
+        match payload.command.as_str() {
+            REGISTER => Ok(ClientCommand::Register {
+                service_token: String::new(),
+                request: acproto::RegisterRequest::decode(payload.payload_data.as_slice())?,
+            }),
+            UNREGISTER => Ok(ClientCommand::Unregister),
+            KEEP_ALIVE => Ok(ClientCommand::KeepAlive(acproto::KeepAliveRequest::decode(
+                payload.payload_data.as_slice(),
+            )?)),
+            PUSH_MESSAGE => Ok(ClientCommand::PushMessageAck),
+            GLOBAL_CS_CMD => {
+                let cmd = acproto::ZtLiveCsCmd::decode(payload.payload_data.as_slice())?;
+                match cmd.cmd_type.as_str() {
+                    ENTER_ROOM => {
+                        self.live_id = cmd.live_id.clone();
+                        self.ticket = cmd.ticket.clone();
+                        Ok(ClientCommand::EnterRoom {
+                            live_id: cmd.live_id,
+                            ticket: cmd.ticket,
+                            enter_room: acproto::ZtLiveCsEnterRoom::decode(cmd.payload.as_slice())?,
+                        })
+                    }
+                    HEARTBEAT => Ok(ClientCommand::Heartbeat {
+                        live_id: cmd.live_id,
+                        ticket: cmd.ticket,
+                        heartbeat: acproto::ZtLiveCsHeartbeat::decode(cmd.payload.as_slice())?,
+                    }),
+                    USER_EXIT => Ok(ClientCommand::UserExit {
+                        live_id: cmd.live_id,
+                        ticket: cmd.ticket,
+                    }),
+                    _ => {
+                        log::trace!("unknown ZtLiveCsCmd cmd_type: {}", cmd.cmd_type);
+                        Ok(ClientCommand::Unknown(payload))
+                    }
+                }
+            }
+            _ => {
+                log::trace!("unknown UpstreamPayload command: {}", payload.command);
+                Ok(ClientCommand::Unknown(payload))
+            }
+        }
+    }
+}
+
+impl Encoder for DanmakuServerProto {
+    type Item = ServerMessage;
+
+    type Error = Error;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<()> {
+        let mut adopt = None;
+        let (mode, payload) = match item {
+            ServerMessage::RegisterResponse {
+                instance_id,
+                session_key,
+            } => {
+                let resp = acproto::RegisterResponse {
+                    sess_key: session_key.clone(),
+                    instance_id,
+                    ..Default::default()
+                };
+                adopt = Some((instance_id, session_key));
+                (
+                    EncryptionMode::KEncryptionServiceToken,
+                    self.downstream(REGISTER, resp.encode_to_vec()),
+                )
+            }
+            ServerMessage::Unregister => (
+                EncryptionMode::KEncryptionSessionKey,
+                self.downstream(UNREGISTER, Vec::new()),
+            ),
+            ServerMessage::KeepAlive => (
+                EncryptionMode::KEncryptionSessionKey,
+                self.downstream(KEEP_ALIVE, Vec::new()),
+            ),
+            ServerMessage::EnterRoomAck {
+                heartbeat_interval_ms,
+            } => (
+                EncryptionMode::KEncryptionSessionKey,
+                self.cmd_ack(
+                    ENTER_ROOM_ACK,
+                    acproto::ZtLiveCsEnterRoomAck {
+                        heartbeat_interval_ms,
+                    }
+                    .encode_to_vec(),
+                ),
+            ),
+            ServerMessage::HeartbeatAck {
+                client_timestamp_ms,
+                client_sequence,
+            } => (
+                EncryptionMode::KEncryptionSessionKey,
+                self.cmd_ack(
+                    HEARTBEAT_ACK,
+                    acproto::ZtLiveCsHeartbeatAck {
+                        server_timestamp_ms: SystemTime::now()
+                            .duration_since(SystemTime::UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_millis() as i64,
+                        client_timestamp_ms,
+                        client_sequence,
+                    }
+                    .encode_to_vec(),
+                ),
+            ),
+            ServerMessage::UserExitAck => (
+                EncryptionMode::KEncryptionSessionKey,
+                self.cmd_ack(USER_EXIT_ACK, Vec::new()),
+            ),
+            ServerMessage::ActionSignal(signal) => (
+                EncryptionMode::KEncryptionSessionKey,
+                self.push_downstream(&self.push(ACTION_SIGNAL, signal.encode_to_vec())?),
+            ),
+            ServerMessage::StateSignal(signal) => (
+                EncryptionMode::KEncryptionSessionKey,
+                self.push_downstream(&self.push(STATE_SIGNAL, signal.encode_to_vec())?),
+            ),
+            ServerMessage::NotifySignal(signal) => (
+                EncryptionMode::KEncryptionSessionKey,
+                self.push_downstream(&self.push(NOTIFY_SIGNAL, signal.encode_to_vec())?),
+            ),
+            ServerMessage::StatusChanged(status) => (
+                EncryptionMode::KEncryptionSessionKey,
+                self.push_downstream(&self.push(STATUS_CHANGED, status.encode_to_vec())?),
+            ),
+            ServerMessage::TicketInvalid => (
+                EncryptionMode::KEncryptionSessionKey,
+                self.push_downstream(&self.push(TICKET_INVALID, Vec::new())?),
+            ),
+            ServerMessage::Stop => (
+                EncryptionMode::KEncryptionSessionKey,
+                acproto::DownstreamPayload {
+                    error_code: 10018,
+                    error_msg: "stop pushing danmaku".to_string(),
+                    ..self.downstream("", Vec::new())
+                },
+            ),
+            ServerMessage::Push(message) => (
+                EncryptionMode::KEncryptionSessionKey,
+                self.push_downstream(&message),
+            ),
+            ServerMessage::Raw(mode, payload) => (mode, payload),
+        };
+
+        let header = acproto::PacketHeader {
+            app_id: self.app_id,
+            uid: self.user_id,
+            instance_id: self.instance_id,
+            encryption_mode: mode.into(),
+            seq_id: self.seq_id,
+            kpn: KPN.to_string(),
+            ..Default::default()
+        };
+        encode_packet(
+            header,
+            &payload.encode_to_vec(),
+            &self.security_key,
+            self.session_key.as_deref(),
+            dst,
+        )?;
+        self.seq_id += 1;
+        if let Some((instance_id, session_key)) = adopt {
+            self.instance_id = instance_id;
+            self.session_key = Some(session_key);
+        }
+
+        Ok(())
+    }
+}
+
+impl Decoder for DanmakuServerProto {
+    type Item = ClientMessage;
+
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>> {
+        let (header, payload) =
+            match decode_packet(src, &self.security_key, self.session_key.as_deref())? {
+                Some(packet) => packet,
+                None => return Ok(None),
+            };
+        let payload = acproto::UpstreamPayload::decode(payload.as_slice())?;
+        let mut command = self.command(payload)?;
+        if let ClientCommand::Register { service_token, .. } = &mut command {
+            self.user_id = header.uid;
+            *service_token = header
+                .token_info
+                .as_ref()
+                .map(|t| String::from_utf8_lossy(&t.token).into_owned())
+                .unwrap_or_default();
+        }
+
+        Ok(Some(ClientMessage { header, command }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::DanmakuToken,
+        danmaku::*,
+        proto::{DanmakuProto, ReceiveMessage, SendMessage},
+    };
+    use std::convert::TryFrom;
+
+    const SECURITY_KEY: &str = "giEyDh9ECKoxyj6kID4eXg==";
+
+    #[test]
+    fn test_server_proto() -> Result<()> {
+        let mut client = DanmakuProto::try_from(DanmakuToken {
+            user_id: 1,
+            liver_uid: 2,
+            security_key: SECURITY_KEY.to_string(),
+            service_token: "service_token".to_string(),
+            live_id: "live_id".to_string(),
+            enter_room_attach: "attach".to_string(),
+            tickets: vec!["ticket".to_string()],
+        })?;
+        let mut server = DanmakuServerProto::new(SECURITY_KEY)?;
+        let mut buf = BytesMut::new();
+
+        client.encode(SendMessage::RegisterRequest, &mut buf)?;
+        let msg = server.decode(&mut buf)?.expect("no register request");
+        assert!(matches!(
+            msg.command,
+            ClientCommand::Register { ref service_token, .. } if service_token == "service_token"
+        ));
+        assert_eq!(server.user_id(), 1);
+
+        server.encode(ServerMessage::register_response(), &mut buf)?;
+        assert_eq!(
+            client.decode(&mut buf)?,
+            Some(ReceiveMessage::RegisterResponse)
+        );
+        assert!(server.session_key().is_some());
+
+        client.encode(SendMessage::ZtLiveCsEnterRoom, &mut buf)?;
+        let msg = server.decode(&mut buf)?.expect("no enter room request");
+        assert!(matches!(
+            msg.command,
+            ClientCommand::EnterRoom { ref live_id, ref ticket, ref enter_room }
+                if live_id == "live_id" && ticket == "ticket" && enter_room.enter_room_attach == "attach"
+        ));
+
+        server.encode(
+            ServerMessage::EnterRoomAck {
+                heartbeat_interval_ms: 1000,
+            },
+            &mut buf,
+        )?;
+        assert_eq!(
+            client.decode(&mut buf)?,
+            Some(ReceiveMessage::Interval(1000))
+        );
+
+        client.encode(SendMessage::ZtLiveCsHeartbeat, &mut buf)?;
+        let msg = server.decode(&mut buf)?.expect("no heartbeat");
+        assert!(matches!(msg.command, ClientCommand::Heartbeat { .. }));
+
+        let comment = acproto::CommonActionSignalComment {
+            content: "comment".to_string(),
+            ..Default::default()
+        };
+        let signal = acproto::ZtLiveScActionSignal {
+            item: vec![acproto::ZtLiveActionSignalItem {
+                signal_type: COMMENT.to_string(),
+                payload: vec![comment.encode_to_vec()],
+            }],
+        };
+        for gzip in [false, true] {
+            let _ = server.set_gzip(gzip);
+            server.encode(ServerMessage::ActionSignal(signal.clone()), &mut buf)?;
+            assert_eq!(
+                client.decode(&mut buf)?,
+                Some(ReceiveMessage::Danmaku(Danmaku::ActionSignal(vec![
+                    ActionSignal::Comment(comment.clone())
+                ])))
+            );
+        }
+
+        server.encode(ServerMessage::Stop, &mut buf)?;
+        assert_eq!(client.decode(&mut buf)?, Some(ReceiveMessage::Stop));
+
+        client.encode(SendMessage::UnregisterRequest, &mut buf)?;
+        let msg = server.decode(&mut buf)?.expect("no unregister request");
+        assert_eq!(msg.command, ClientCommand::Unregister);
+        assert!(buf.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,494 @@
+//! Records the FLV stream of a live to files.
+//!
+//! The stream is split into parts at tag boundaries, every part starts with the FLV header, the
+//! metadata and the sequence headers, and its timestamps start from zero.
+
+use crate::{
+    client::ReconnectPolicy,
+    danmaku::Danmaku,
+    flv::{Demuxer, FlvHeader, FlvItem, Tag, TagType},
+    Error, Result,
+};
+use acfunliveapi::{client::ApiClient, pretend, stream::StreamSelector};
+use async_trait::async_trait;
+use blocking::{unblock, Unblock};
+use futures::{
+    future::{self, Either},
+    AsyncWriteExt, Future, Stream, StreamExt,
+};
+use futures_timer::Delay;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, ErrorKind},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+#[cfg(feature = "default_http_client")]
+pub use httpclient::HttpStreamClient;
+
+#[cfg(feature = "default_http_client")]
+pub type DefaultStreamRecorder = StreamRecorder<HttpStreamClient>;
+
+/// The body of an HTTP GET request, received chunk by chunk.
+#[async_trait]
+pub trait HttpStream: Sized + Send {
+    async fn get(url: &str) -> Result<Self>;
+
+    /// Returns the next chunk of the body, `None` if the body ended.
+    async fn chunk(&mut self) -> Result<Option<Vec<u8>>>;
+}
+
+#[cfg(feature = "default_http_client")]
+mod httpclient {
+    use super::*;
+
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[derive(Debug)]
+    pub struct HttpStreamClient(reqwest::Response);
+
+    #[async_trait]
+    impl HttpStream for HttpStreamClient {
+        async fn get(url: &str) -> Result<Self> {
+            // live streams never end, so only connecting has a timeout
+            let response = reqwest::Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .build()?
+                .get(url)
+                .send()
+                .await?
+                .error_for_status()?;
+
+            Ok(Self(response))
+        }
+
+        #[inline]
+        async fn chunk(&mut self) -> Result<Option<Vec<u8>>> {
+            Ok(self.0.chunk().await?.map(|c| c.to_vec()))
+        }
+    }
+}
+
+/// Whether it's the metadata or a sequence header, which are needed at the start of a part.
+#[inline]
+fn is_header(tag: &Tag) -> bool {
+    tag.tag_type == TagType::Script || tag.is_sequence_header()
+}
+
+/// Creates `{dir}/{name}_{index}.flv` without overwriting existing files, skipping to the next
+/// free index.
+fn create_part(dir: &Path, name: &str, mut index: usize) -> io::Result<(PathBuf, File)> {
+    loop {
+        let path = dir.join(format!("{}_{:03}.flv", name, index));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => index += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// A part file, which is written in a blocking thread pool.
+#[derive(Debug)]
+struct Part {
+    writer: Unblock<File>,
+    size: u64,
+    /// The timestamp of the first media tag in the part.
+    base: Option<u32>,
+}
+
+impl Part {
+    async fn write_tag(&mut self, mut tag: Tag) -> Result<()> {
+        let base = *self.base.get_or_insert(tag.timestamp);
+        tag.timestamp = tag.timestamp.saturating_sub(base);
+        self.write(&tag.to_bytes()?).await
+    }
+
+    #[inline]
+    async fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.writer.write_all(data).await?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+}
+
+/// Writes the stream of one connection to parts.
+#[derive(Debug)]
+struct PartWriter<'a> {
+    dir: &'a Path,
+    name: String,
+    max_file_size: Option<u64>,
+    max_duration: Option<Duration>,
+    demuxer: Demuxer,
+    header: FlvHeader,
+    header_tags: Vec<Tag>,
+    part: Option<Part>,
+    received: bool,
+}
+
+impl<'a> PartWriter<'a> {
+    fn new<H>(recorder: &'a StreamRecorder<H>, name: String) -> Self {
+        Self {
+            dir: &recorder.dir,
+            name,
+            max_file_size: recorder.max_file_size,
+            max_duration: recorder.max_duration,
+            demuxer: Demuxer::new(),
+            header: FlvHeader::default(),
+            header_tags: Vec::new(),
+            part: None,
+            received: false,
+        }
+    }
+
+    fn should_split(&self, part: &Part, tag: &Tag) -> bool {
+        // parts of a stream with video start at keyframes
+        if self
+            .header_tags
+            .iter()
+            .any(|t| t.tag_type == TagType::Video)
+            && !tag.is_keyframe()
+        {
+            return false;
+        }
+        let duration = tag.timestamp.saturating_sub(part.base.unwrap_or_default());
+        matches!(self.max_file_size, Some(max) if part.size >= max)
+            || matches!(self.max_duration, Some(max) if u128::from(duration) >= max.as_millis())
+    }
+
+    async fn open_part(&mut self, files: &mut Vec<PathBuf>) -> Result<&mut Part> {
+        self.finish().await?;
+        let (dir, name, index) = (self.dir.to_path_buf(), self.name.clone(), files.len());
+        let (path, file) = unblock(move || create_part(&dir, &name, index)).await?;
+        let mut part = Part {
+            writer: Unblock::new(file),
+            size: 0,
+            base: None,
+        };
+        files.push(path);
+        part.write(&self.header.to_bytes()).await?;
+        for tag in &self.header_tags {
+            let mut tag = tag.clone();
+            tag.timestamp = 0;
+            part.write(&tag.to_bytes()?).await?;
+        }
+
+        Ok(self.part.insert(part))
+    }
+
+    async fn write(&mut self, chunk: &[u8], files: &mut Vec<PathBuf>) -> Result<()> {
+        self.received = true;
+        self.demuxer.push(chunk);
+        while let Some(item) = self.demuxer.next_item()? {
+            let tag = match item {
+                FlvItem::Header(header) => {
+                    self.header = header;
+                    continue;
+                }
+                FlvItem::Tag(tag) => tag,
+            };
+            if is_header(&tag) {
+                self.header_tags.retain(|t| t.tag_type != tag.tag_type);
+                self.header_tags.push(tag.clone());
+                if let Some(part) = self.part.as_mut() {
+                    part.write_tag(tag).await?;
+                }
+                continue;
+            }
+            let split = match &self.part {
+                Some(part) => self.should_split(part, &tag),
+                None => true,
+            };
+            let part = match self.part.as_mut() {
+                Some(part) if !split => part,
+                _ => self.open_part(files).await?,
+            };
+            part.write_tag(tag).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        if let Some(mut part) = self.part.take() {
+            part.writer.close().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Downloads the FLV stream of a live to `{dir}/{liver_uid}_{live_id}_{index}.flv`.
+///
+/// A new part is started after reconnecting, or when the part reaches the max file size or duration.
+/// Existing files are never overwritten, the part takes the next free index instead.
+#[derive(Clone, Debug)]
+pub struct StreamRecorder<H> {
+    dir: PathBuf,
+    selector: StreamSelector,
+    max_file_size: Option<u64>,
+    max_duration: Option<Duration>,
+    reconnect: Option<ReconnectPolicy>,
+    http: PhantomData<fn() -> H>,
+}
+
+impl<H: HttpStream> StreamRecorder<H> {
+    #[inline]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            selector: StreamSelector::new().media_type("flv"),
+            max_file_size: None,
+            max_duration: None,
+            reconnect: Some(ReconnectPolicy::default()),
+            http: PhantomData,
+        }
+    }
+
+    /// Selects the stream to record, the default selects the default FLV stream.
+    #[inline]
+    pub fn selector(mut self, selector: StreamSelector) -> Self {
+        self.selector = selector;
+        self
+    }
+
+    /// Starts a new part when the part reaches the size in bytes.
+    #[inline]
+    pub fn max_file_size(mut self, size: Option<u64>) -> Self {
+        self.max_file_size = size;
+        self
+    }
+
+    /// Starts a new part when the part reaches the duration, measured by the timestamps of tags.
+    #[inline]
+    pub fn max_duration(mut self, duration: Option<Duration>) -> Self {
+        self.max_duration = duration;
+        self
+    }
+
+    /// Sets the policy of reconnecting after the stream was disconnected, the default is
+    /// `ReconnectPolicy::default()`. The URL of the stream is got again before reconnecting.
+    #[inline]
+    pub fn reconnect_policy(mut self, policy: Option<ReconnectPolicy>) -> Self {
+        self.reconnect = policy;
+        self
+    }
+
+    async fn download<C>(
+        &self,
+        api: &ApiClient<C>,
+        liver_uid: i64,
+        files: &mut Vec<PathBuf>,
+        received: &mut bool,
+    ) -> Result<()>
+    where
+        C: pretend::client::Client + Send + Sync,
+    {
+        let info = api.get_live_info(liver_uid).await?;
+        let stream = self
+            .selector
+            .select_stream(&info.data.video_play_res)
+            .ok_or(Error::NoStream)?;
+        let mut body = H::get(&stream.url).await?;
+        let mut writer = PartWriter::new(self, format!("{}_{}", liver_uid, info.data.live_id));
+        let result = loop {
+            match body.chunk().await {
+                Ok(Some(chunk)) => {
+                    if let Err(e) = writer.write(&chunk, files).await {
+                        break Err(e);
+                    }
+                }
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        *received = writer.received;
+        writer.finish().await?;
+
+        result
+    }
+
+    /// Records the live of the liver until `stop` resolves or the live ended, returns the
+    /// paths of the recorded parts.
+    ///
+    /// Pass `live_closed()` of the danmaku client as `stop` to stop when the danmaku server reports
+    /// that the live was closed.
+    pub async fn record<C, F>(
+        &self,
+        api: &ApiClient<C>,
+        liver_uid: i64,
+        stop: F,
+    ) -> Result<Vec<PathBuf>>
+    where
+        C: pretend::client::Client + Send + Sync,
+        F: Future<Output = ()>,
+    {
+        let mut stop = Box::pin(stop);
+        let mut files = Vec::new();
+        let mut attempt = 0;
+        loop {
+            let mut received = false;
+            let download = Box::pin(self.download(api, liver_uid, &mut files, &mut received));
+            let result = match future::select(stop.as_mut(), download).await {
+                Either::Left(_) => None,
+                Either::Right((result, _)) => Some(result),
+            };
+            let result = match result {
+                None => break,
+                Some(Err(Error::AcFunLiveApiError(acfunliveapi::Error::LiveNotStarted(_)))) => {
+                    break
+                }
+                Some(result) => result,
+            };
+            if received {
+                attempt = 0;
+            }
+            let policy = match self.reconnect {
+                Some(policy) if policy.can_retry(attempt) => policy,
+                _ => return result.map(|_| files),
+            };
+            if let Err(e) = result {
+                log::debug!("failed to record the stream of liver {}: {}", liver_uid, e);
+            }
+            let delay = Delay::new(policy.delay(attempt));
+            if let Either::Left(_) = future::select(stop.as_mut(), delay).await {
+                break;
+            }
+            attempt += 1;
+        }
+
+        Ok(files)
+    }
+}
+
+/// Resolves when the danmaku stream reports that the live was closed or banned, or the stream ended.
+pub async fn live_closed<S, E>(mut danmaku: S)
+where
+    S: Stream<Item = std::result::Result<Danmaku, E>> + Unpin,
+{
+    while let Some(danmaku) = danmaku.next().await {
+        if matches!(danmaku, Ok(Danmaku::StatusChanged(status)) if status.is_ended()) {
+            break;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "default_http_client"))]
+mod tests {
+    use super::*;
+    use crate::flv::{tests::sample_flv, FlvReader};
+    use acfunliveapi::{
+        client::ApiClientBuilder,
+        mock::*,
+        pretend::client::{Bytes, Method},
+    };
+    use std::{
+        fs,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    const START_PLAY: &str = "/rest/zt/live/web/startPlay";
+
+    #[tokio::test]
+    async fn test_stream_recorder() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/live.flv", listener.local_addr()?);
+        let mock = MockClient::new();
+        let _ = mock.route(
+            Method::POST,
+            START_PLAY,
+            MockResponse::json(Bytes::from(
+                LIVE_INFO.replace("https://mock.cdn/live/stream_1_high.flv", &url),
+            )),
+        );
+        let api = ApiClientBuilder::new(mock.clone())?.build().await?;
+
+        // serves the stream twice, the live ends after the second time
+        let connections = Arc::new(AtomicUsize::new(0));
+        let server_connections = connections.clone();
+        let server = tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await?;
+                let mut request = [0; 1024];
+                let _ = socket.read(&mut request).await?;
+                socket
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: video/x-flv\r\nConnection: close\r\n\r\n")
+                    .await?;
+                socket.write_all(&sample_flv(10_000)).await?;
+                if server_connections.fetch_add(1, Ordering::SeqCst) == 1 {
+                    let _ = mock.json(Method::POST, START_PLAY, LIVE_NOT_STARTED);
+                    let _ = mock.json(Method::GET, "/api/live/info", USER_LIVE_OFFLINE);
+                }
+                socket.shutdown().await?;
+            }
+            #[allow(unreachable_code)]
+            std::io::Result::Ok(())
+        });
+
+        let dir = std::env::temp_dir().join(format!("acfun_stream_record_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let recorder = DefaultStreamRecorder::new(&dir)
+            .max_duration(Some(Duration::from_secs(2)))
+            .reconnect_policy(Some(ReconnectPolicy {
+                initial_delay: Duration::from_millis(10),
+                ..Default::default()
+            }));
+        let files = recorder
+            .record(&api, MOCK_LIVER_UID, future::pending())
+            .await?;
+        server.abort();
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+        // 0-1s, 2-3s and 4s in every connection
+        assert_eq!(files.len(), 6);
+        for file in &files {
+            let data = fs::read(file)?;
+            let mut reader = FlvReader::new(data.as_slice());
+            let items = reader.by_ref().collect::<Result<Vec<_>>>()?;
+            assert!(!reader.is_truncated());
+            assert!(matches!(items[0], FlvItem::Header(_)));
+            let tags: Vec<_> = items[1..]
+                .iter()
+                .filter_map(|i| match i {
+                    FlvItem::Tag(tag) => Some(tag),
+                    FlvItem::Header(_) => None,
+                })
+                .collect();
+            assert_eq!(tags.iter().take_while(|t| is_header(t)).count(), 3);
+            let media = tags[3];
+            assert!(media.is_keyframe());
+            assert_eq!(media.timestamp, 0);
+        }
+        assert_eq!(
+            fs::read(&files[0])?.len(),
+            fs::read(&files[3])?.len(),
+            "{:?}",
+            files
+        );
+
+        // a restarted recording skips the existing parts
+        let (path, _) = create_part(&dir, "part", 0)?;
+        assert!(path.ends_with("part_000.flv"));
+        let (path, _) = create_part(&dir, "part", 0)?;
+        assert!(path.ends_with("part_001.flv"));
+        fs::remove_dir_all(&dir)?;
+
+        // stops when the live is closed
+        let danmaku = futures::stream::iter(vec![
+            Ok::<_, Error>(Danmaku::StatusChanged(
+                crate::danmaku::LiveStatus::UrlChanged,
+            )),
+            Ok(Danmaku::StatusChanged(crate::danmaku::LiveStatus::Closed)),
+        ]);
+        live_closed(danmaku).await;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,414 @@
+//! Watches a liver and receives the danmaku of every live automatically.
+
+use crate::{client::*, danmaku::Danmaku, global::*, websocket::WebSocket, Result};
+use acfunliveapi::{client::ApiClient, pretend, response::UserLiveInfo};
+use futures::{ready, stream::FusedStream, Future, Stream, StreamExt};
+use futures_timer::Delay;
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    fmt,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+#[cfg(feature = "default_ws_client")]
+use crate::websocket::WebSocketClient;
+
+#[cfg(feature = "default_ws_client")]
+pub type DefaultLiveWatcher<C> = LiveWatcher<C, WebSocketClient>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WatchEvent {
+    /// The liver started a live, the watcher is joining the live room.
+    LiveStarted(Box<UserLiveInfo>),
+    Danmaku(Danmaku),
+    /// The live ended and the watcher left the live room.
+    LiveEnded {
+        live_id: String,
+    },
+}
+
+/// Polls `get_user_live_info()` of the liver on an interval, joins the live room when the liver
+/// starts a live and leaves it when the live ends. The live info is also polled while in the live
+/// room, so a live which ended without the danmaku server telling it is noticed.
+///
+/// Errors are yielded without ending the stream, the watcher retries after the interval.
+pub struct LiveWatcher<C, W: WebSocket> {
+    api: Arc<ApiClient<C>>,
+    liver_uid: i64,
+    interval: Duration,
+    server: String,
+    reconnect: Option<ReconnectPolicy>,
+    live_id: Option<String>,
+    ended_live_id: Option<String>,
+    /// Whether the watcher is joining the current live again.
+    rejoining: bool,
+    events: VecDeque<WatchEvent>,
+    client: Option<DanmakuClient<W>>,
+    delay: Option<Delay>,
+    query: Option<Pending<Result<UserLiveInfo>>>,
+    connecting: Option<Pending<std::result::Result<DanmakuClient<W>, W::Error>>>,
+}
+
+impl<C, W: WebSocket> fmt::Debug for LiveWatcher<C, W> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LiveWatcher")
+            .field("liver_uid", &self.liver_uid)
+            .field("interval", &self.interval)
+            .field("server", &self.server)
+            .field("live_id", &self.live_id)
+            .finish()
+    }
+}
+
+impl<C, W> LiveWatcher<C, W>
+where
+    C: pretend::client::Client + Send + Sync + 'static,
+    W: WebSocket + Send + 'static,
+    W::Error: Send,
+{
+    #[inline]
+    pub fn new(api: Arc<ApiClient<C>>, liver_uid: i64) -> Self {
+        Self {
+            api,
+            liver_uid,
+            interval: Duration::from_secs(10),
+            server: DANMAKU_SERVER.to_string(),
+            reconnect: None,
+            live_id: None,
+            ended_live_id: None,
+            rejoining: false,
+            events: VecDeque::new(),
+            client: None,
+            delay: None,
+            query: None,
+            connecting: None,
+        }
+    }
+
+    /// Sets the interval of polling the live info, the default is 10 seconds.
+    #[inline]
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Connects to the specified danmaku server instead of AcFun's.
+    #[inline]
+    pub fn server<'a>(mut self, server: impl Into<Cow<'a, str>>) -> Self {
+        self.server = server.into().into_owned();
+        self
+    }
+
+    #[inline]
+    pub fn reconnect_policy(mut self, policy: Option<ReconnectPolicy>) -> Self {
+        self.reconnect = policy;
+        self
+    }
+
+    #[inline]
+    pub fn liver_uid(&self) -> i64 {
+        self.liver_uid
+    }
+
+    /// The ID of the current live, `None` if the liver isn't living.
+    #[inline]
+    pub fn live_id(&self) -> Option<&str> {
+        self.live_id.as_deref()
+    }
+
+    #[inline]
+    pub fn danmaku_client(&self) -> Option<&DanmakuClient<W>> {
+        self.client.as_ref()
+    }
+
+    #[inline]
+    pub fn danmaku_client_mut(&mut self) -> Option<&mut DanmakuClient<W>> {
+        self.client.as_mut()
+    }
+
+    fn query(&self) -> Pending<Result<UserLiveInfo>> {
+        let api = self.api.clone();
+        let liver_uid = self.liver_uid;
+        Pending(Box::pin(async move {
+            Ok(api.get_user_live_info(liver_uid).await?)
+        }))
+    }
+
+    fn connect(&self) -> Pending<std::result::Result<DanmakuClient<W>, W::Error>> {
+        let api = self.api.clone();
+        let liver_uid = self.liver_uid;
+        let server = self.server.clone();
+        let reconnect = self.reconnect;
+        Pending(Box::pin(async move {
+            let token = DanmakuToken::from_api_client(&api, liver_uid).await?;
+            let mut client = DanmakuClient::<W>::with_server(token, server).await?;
+            let _ = client
+                .set_reconnect_policy(reconnect)
+                .set_token_refresher(Some(api));
+            Ok(client)
+        }))
+    }
+
+    #[inline]
+    fn wait(&mut self) {
+        self.delay = Some(Delay::new(self.interval));
+    }
+
+    fn live_ended(&mut self) {
+        self.rejoining = false;
+        if let Some(live_id) = self.live_id.take() {
+            self.events.push_back(WatchEvent::LiveEnded {
+                live_id: live_id.clone(),
+            });
+            self.ended_live_id = Some(live_id);
+        }
+    }
+
+    fn update(&mut self, info: UserLiveInfo) {
+        match info.live_data.as_ref().map(|d| d.live_id.clone()) {
+            // the live info may still show the ended live for a while
+            Some(live_id) if self.ended_live_id.as_ref() == Some(&live_id) => self.wait(),
+            // still in the live room
+            Some(live_id) if self.client.is_some() && self.live_id.as_ref() == Some(&live_id) => {
+                self.wait()
+            }
+            Some(live_id) => {
+                if self.live_id.as_ref() == Some(&live_id) {
+                    // the danmaku client was closed before the live ended
+                    self.rejoining = true;
+                } else {
+                    // leaves the room of the last live, which ended without the danmaku server telling it
+                    self.client = None;
+                    self.live_ended();
+                    log::trace!("liver {} started live {}", self.liver_uid, live_id);
+                    self.live_id = Some(live_id);
+                    self.events
+                        .push_back(WatchEvent::LiveStarted(Box::new(info)));
+                }
+                self.connecting = Some(self.connect());
+            }
+            None => {
+                self.client = None;
+                self.live_ended();
+                self.ended_live_id = None;
+                self.wait();
+            }
+        }
+    }
+}
+
+impl<C, W> Stream for LiveWatcher<C, W>
+where
+    C: pretend::client::Client + Send + Sync + 'static,
+    W: WebSocket + Send + 'static,
+    W::Error: Send,
+{
+    type Item = std::result::Result<WatchEvent, W::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            if let Some(client) = self.client.as_mut() {
+                match client.poll_next_unpin(cx) {
+                    Poll::Ready(Some(Ok(danmaku))) => {
+                        if let Danmaku::StatusChanged(status) = &danmaku {
+                            if status.is_ended() {
+                                self.events.push_back(WatchEvent::Danmaku(danmaku));
+                                self.live_ended();
+                                continue;
+                            }
+                        }
+                        return Poll::Ready(Some(Ok(WatchEvent::Danmaku(danmaku))));
+                    }
+                    Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                    Poll::Ready(None) => {
+                        self.client = None;
+                        self.delay = None;
+                        self.query = Some(self.query());
+                        continue;
+                    }
+                    // keeps polling the live info while waiting for danmaku
+                    Poll::Pending => {}
+                }
+            }
+            if let Some(connecting) = self.connecting.as_mut() {
+                let result = ready!(connecting.0.as_mut().poll(cx));
+                self.connecting = None;
+                match result {
+                    Ok(client) => {
+                        self.client = Some(client);
+                        self.wait();
+                        if std::mem::take(&mut self.rejoining) {
+                            self.events
+                                .push_back(WatchEvent::Danmaku(Danmaku::Reconnected));
+                        }
+                    }
+                    Err(e) => {
+                        self.wait();
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                }
+                continue;
+            }
+            if let Some(query) = self.query.as_mut() {
+                let result = ready!(query.0.as_mut().poll(cx));
+                self.query = None;
+                match result {
+                    Ok(info) => self.update(info),
+                    Err(e) => {
+                        self.wait();
+                        return Poll::Ready(Some(Err(e.into())));
+                    }
+                }
+                continue;
+            }
+            if let Some(delay) = self.delay.as_mut() {
+                ready!(Pin::new(delay).poll(cx));
+                self.delay = None;
+            }
+            self.query = Some(self.query());
+        }
+    }
+}
+
+impl<C, W> FusedStream for LiveWatcher<C, W>
+where
+    C: pretend::client::Client + Send + Sync + 'static,
+    W: WebSocket + Send + 'static,
+    W::Error: Send,
+{
+    /// The watcher never ends.
+    #[inline]
+    fn is_terminated(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(feature = "default_ws_client")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{acproto, danmaku::*, mock::*};
+    use acfunliveapi::{
+        client::ApiClientBuilder,
+        mock::{self as api_mock, MockClient},
+        pretend::client::Method,
+    };
+    use prost::Message;
+    use tokio::time::{sleep, timeout};
+
+    async fn next(watcher: &mut DefaultLiveWatcher<MockClient>) -> Result<WatchEvent> {
+        timeout(Duration::from_secs(10), watcher.next())
+            .await
+            .expect("timeout when watching the liver")
+            .expect("the watcher ended")
+    }
+
+    #[tokio::test]
+    async fn test_live_watcher() -> Result<()> {
+        let mock = MockClient::new();
+        let api = ApiClientBuilder::new(mock.clone())?.build().await?;
+        let token = DanmakuToken::from_api_client(&api, api_mock::MOCK_LIVER_UID).await?;
+        let comment = acproto::CommonActionSignalComment {
+            content: "comment".to_string(),
+            ..Default::default()
+        };
+        let server = MockDanmakuServer::bind().await?.with_token(token);
+        let url = server.url();
+        let server = server
+            .push_action_signal(COMMENT, comment.encode_to_vec())
+            .push_status_changed(acproto::ZtLiveScStatusChanged {
+                r#type: acproto::zt_live_sc_status_changed::Type::LiveClosed.into(),
+                ..Default::default()
+            })
+            .spawn();
+
+        let _ = mock.json(Method::GET, "/api/live/info", api_mock::USER_LIVE_OFFLINE);
+        let mut watcher = DefaultLiveWatcher::new(Arc::new(api), api_mock::MOCK_LIVER_UID)
+            .interval(Duration::from_millis(20))
+            .server(url);
+        let live = tokio::spawn(async move {
+            sleep(Duration::from_millis(100)).await;
+            let _ = mock.json(Method::GET, "/api/live/info", api_mock::USER_LIVE_INFO);
+        });
+
+        match next(&mut watcher).await? {
+            WatchEvent::LiveStarted(info) => assert_eq!(
+                info.live_data.expect("no live data").live_id,
+                api_mock::MOCK_LIVE_ID
+            ),
+            event => panic!("unexpected event: {:?}", event),
+        }
+        assert_eq!(
+            next(&mut watcher).await?,
+            WatchEvent::Danmaku(Danmaku::ActionSignal(vec![ActionSignal::Comment(comment)]))
+        );
+        assert_eq!(
+            next(&mut watcher).await?,
+            WatchEvent::Danmaku(Danmaku::StatusChanged(LiveStatus::Closed))
+        );
+        assert_eq!(
+            next(&mut watcher).await?,
+            WatchEvent::LiveEnded {
+                live_id: api_mock::MOCK_LIVE_ID.to_string()
+            }
+        );
+        // the ended live is still shown in the live info, so the watcher doesn't join it again
+        assert!(timeout(Duration::from_millis(200), next(&mut watcher))
+            .await
+            .is_err());
+        assert_eq!(watcher.live_id(), None);
+        live.await.expect("failed to change the mock route");
+        server.abort();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_live_watcher_polling_in_room() -> Result<()> {
+        let mock = MockClient::new();
+        let api = ApiClientBuilder::new(mock.clone())?.build().await?;
+        let token = DanmakuToken::from_api_client(&api, api_mock::MOCK_LIVER_UID).await?;
+        let comment = acproto::CommonActionSignalComment {
+            content: "comment".to_string(),
+            ..Default::default()
+        };
+        // the danmaku server never tells that the live was closed
+        let server = MockDanmakuServer::bind().await?.with_token(token);
+        let url = server.url();
+        let server = server
+            .push_action_signal(COMMENT, comment.encode_to_vec())
+            .spawn();
+
+        let mut watcher = DefaultLiveWatcher::new(Arc::new(api), api_mock::MOCK_LIVER_UID)
+            .interval(Duration::from_millis(20))
+            .server(url);
+        assert!(matches!(
+            next(&mut watcher).await?,
+            WatchEvent::LiveStarted(_)
+        ));
+        assert_eq!(
+            next(&mut watcher).await?,
+            WatchEvent::Danmaku(Danmaku::ActionSignal(vec![ActionSignal::Comment(comment)]))
+        );
+        assert!(watcher.danmaku_client().is_some());
+        let _ = mock.json(Method::GET, "/api/live/info", api_mock::USER_LIVE_OFFLINE);
+        assert_eq!(
+            next(&mut watcher).await?,
+            WatchEvent::LiveEnded {
+                live_id: api_mock::MOCK_LIVE_ID.to_string()
+            }
+        );
+        assert!(watcher.danmaku_client().is_none());
+        server.abort();
+
+        Ok(())
+    }
+}
@@ -130,15 +130,29 @@ mod tests {
     use super::*;
     use crate::Result;
     use futures::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use ws_stream_tungstenite::WsStream;
 
     #[tokio::test]
     async fn test_websocket() -> Result<()> {
-        let mut client = WebSocketClient::connect("ws://echo.websocket.org/").await?;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("ws://{}/", listener.local_addr()?);
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let ws = WsStream::new(async_tungstenite::tokio::accept_async(stream).await?);
+            let (mut reader, mut writer) = ws.split();
+            // the write half is already closed when the client closes the connection
+            let _ = futures::io::copy(&mut reader, &mut writer).await;
+            Result::Ok(())
+        });
+
+        let mut client = WebSocketClient::connect(url).await?;
         client.write_all(b"hello").await?;
         let mut msg = [0u8; 5];
         client.read_exact(&mut msg).await?;
         assert_eq!(&msg, b"hello");
         client.close().await?;
+        server.await.expect("failed to run the echo server")?;
 
         Ok(())
     }